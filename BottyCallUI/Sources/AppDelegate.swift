@@ -62,6 +62,9 @@ class AppDelegate: NSObject, NSApplicationDelegate {
         connection.onConnected = { [weak self] connected in
             self?.sidebarView.setConnected(connected)
         }
+        connection.onError = { [weak self] message in
+            self?.sidebarView.setError(message)
+        }
         connection.start()
 
         NotificationCenter.default.addObserver(
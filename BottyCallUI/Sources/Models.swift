@@ -78,6 +78,16 @@ struct SessionGroup {
     var entries: [SessionEntry]
 }
 
+/// Must match `PROTOCOL_VERSION` in the daemon's src/protocol.rs.
+let protocolVersion = 1
+
+/// The daemon's first reply to a versioned hello: `welcome` or `error`.
+struct HandshakeReply: Decodable {
+    let type: String
+    let version: Int?
+    let message: String?
+}
+
 enum ServerMessage: Decodable {
     case snapshot(sessions: [Session])
     case update(session: Session)
@@ -1,13 +1,20 @@
 import Foundation
 
 class SessionConnection {
-    private let socketPath = "/tmp/bottycall.sock"
+    private let socketPath: String = {
+        let env = ProcessInfo.processInfo.environment
+        if let path = env["BOTTYCALL_SOCKET"], !path.isEmpty { return path }
+        if let dir = env["XDG_RUNTIME_DIR"], !dir.isEmpty { return dir + "/bottycall.sock" }
+        return "/tmp/bottycall-\(getuid())/bottycall.sock"
+    }()
     private let queue = DispatchQueue(label: "bottycall.connection", qos: .utility)
     private var fd: Int32 = -1
     private var running = false
 
     var onMessage: ((ServerMessage) -> Void)?
     var onConnected: ((Bool) -> Void)?
+    /// Called with the daemon's reason for refusing the connection, or nil once it accepts.
+    var onError: ((String?) -> Void)?
 
     private lazy var decoder: JSONDecoder = {
         let d = JSONDecoder()
@@ -50,7 +57,15 @@ class SessionConnection {
         }
     }
 
+    /// Whoever owns the socket receives every session update; never talk to one that
+    /// another user created.
+    private func ownedByUs() -> Bool {
+        var info = stat()
+        return stat(socketPath, &info) == 0 && info.st_uid == getuid()
+    }
+
     private func tryConnect() -> Bool {
+        guard ownedByUs() else { return false }
         fd = socket(AF_UNIX, SOCK_STREAM, 0)
         guard fd >= 0 else { return false }
 
@@ -78,7 +93,8 @@ class SessionConnection {
             return false
         }
 
-        let msg = "{\"type\":\"subscribe\"}\n"
+        let msg = "{\"type\":\"subscribe\",\"version\":\(protocolVersion),"
+            + "\"capabilities\":[\"subscribe\"]}\n"
         let sent = msg.withCString { Darwin.write(fd, $0, msg.utf8.count) }
         guard sent == msg.utf8.count else {
             Darwin.close(fd)
@@ -89,11 +105,31 @@ class SessionConnection {
         return true
     }
 
+    /// Check the first line from the daemon. Returns false if the connection was refused;
+    /// a daemon predating versioning sends no welcome, so other messages pass through.
+    private func handleHandshake(_ line: Data) -> Bool {
+        guard let reply = try? decoder.decode(HandshakeReply.self, from: line) else { return true }
+        let error: String?
+        switch reply.type {
+        case "welcome" where reply.version == protocolVersion:
+            error = nil
+        case "welcome":
+            error = "daemon speaks protocol v\(reply.version ?? 0), this app v\(protocolVersion)"
+        case "error":
+            error = reply.message ?? "daemon refused the connection"
+        default:
+            return true
+        }
+        DispatchQueue.main.async { [weak self] in self?.onError?(error) }
+        return error == nil
+    }
+
     private func readLoop() {
         var buffer = Data()
         var readBuf = [UInt8](repeating: 0, count: 4096)
+        var handshakeDone = false
 
-        while running && fd >= 0 {
+        reading: while running && fd >= 0 {
             let n = Darwin.read(fd, &readBuf, readBuf.count)
             if n <= 0 { break }
             buffer.append(readBuf, count: n)
@@ -103,6 +139,10 @@ class SessionConnection {
                 buffer = Data(buffer[buffer.index(after: newline)...])
 
                 guard !lineData.isEmpty else { continue }
+                if !handshakeDone {
+                    handshakeDone = true
+                    guard handleHandshake(Data(lineData)) else { break reading }
+                }
                 if let msg = try? decoder.decode(ServerMessage.self, from: Data(lineData)) {
                     DispatchQueue.main.async { [weak self] in
                         self?.onMessage?(msg)
@@ -8,6 +8,7 @@ class SidebarView: NSView {
     private let scrollView = NSScrollView()
     private let contentView = FlippedView()
     private let emptyLabel = NSTextField(labelWithString: "No sessions")
+    private var errorMessage: String?
     private let leftBorder = NSView()
 
     private var rowViews: [SessionRowView] = []
@@ -83,6 +84,7 @@ class SidebarView: NSView {
         subtitleLabel.drawsBackground = false
         subtitleLabel.isEditable = false
         subtitleLabel.isSelectable = false
+        subtitleLabel.lineBreakMode = .byTruncatingTail
 
         topSeparator.wantsLayer = true
         topSeparator.layer?.backgroundColor = NSColor(white: 0.28, alpha: 1).cgColor
@@ -244,8 +246,18 @@ class SidebarView: NSView {
         contentView.frame.size.width = w
     }
 
+    /// Show why the daemon refused the connection, until it accepts one again.
+    func setError(_ message: String?) {
+        errorMessage = message
+        subtitleLabel.toolTip = message
+        setConnected(message == nil)
+    }
+
     func setConnected(_ connected: Bool) {
-        if !connected && sessions.isEmpty {
+        if let errorMessage {
+            subtitleLabel.stringValue = errorMessage
+            subtitleLabel.textColor = NSColor(red: 0.85, green: 0.35, blue: 0.35, alpha: 1)
+        } else if !connected && sessions.isEmpty {
             subtitleLabel.stringValue = "connecting"
             subtitleLabel.textColor = NSColor(white: 0.38, alpha: 1)
         } else {
@@ -0,0 +1,131 @@
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::protocol::{ClientHello, Hello, ServerMessage, check_handshake, check_socket_owner};
+use crate::session::{Session, Status, base_slug, slug_from_cwd};
+
+/// A handshaken connection to the daemon, read one `ServerMessage` line at a time.
+pub struct Connection {
+    reader: BufReader<UnixStream>,
+    /// First message from a daemon that predates the handshake.
+    pending: Option<ServerMessage>,
+}
+
+impl Connection {
+    /// Connect, send `request` with our protocol version and check the daemon's reply.
+    pub fn open(socket: &Path, request: ClientHello) -> anyhow::Result<Self> {
+        check_socket_owner(socket)?;
+        let mut stream = UnixStream::connect(socket)
+            .map_err(|e| anyhow::anyhow!("cannot reach daemon at {}: {e}", socket.display()))?;
+        stream.write_all(Hello::new(request).to_line()?.as_bytes())?;
+
+        let mut conn = Self {
+            reader: BufReader::new(stream),
+            pending: None,
+        };
+        let first = conn
+            .recv()?
+            .ok_or_else(|| anyhow::anyhow!("daemon closed the connection"))?;
+        conn.pending = check_handshake(first)?;
+        Ok(conn)
+    }
+
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> anyhow::Result<()> {
+        self.reader.get_ref().set_read_timeout(timeout)?;
+        Ok(())
+    }
+
+    /// Next message, or `None` once the daemon closes the connection.
+    pub fn recv(&mut self) -> anyhow::Result<Option<ServerMessage>> {
+        if let Some(msg) = self.pending.take() {
+            return Ok(Some(msg));
+        }
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            return Ok(Some(serde_json::from_str(line.trim())?));
+        }
+    }
+}
+
+/// Connect to the daemon, subscribe, and return the initial snapshot.
+pub fn fetch_snapshot(socket: &Path) -> anyhow::Result<Vec<Session>> {
+    let mut conn = Connection::open(socket, ClientHello::Subscribe)?;
+    conn.set_read_timeout(Some(Duration::from_secs(2)))?;
+    match conn.recv()? {
+        Some(ServerMessage::Snapshot { sessions }) => Ok(sessions),
+        _ => anyhow::bail!("daemon did not send a snapshot"),
+    }
+}
+
+/// A `key=value[,value...]` session filter from the command line.
+/// Values of one filter are alternatives; separate filters must all match.
+#[derive(Debug, Clone)]
+pub enum Filter {
+    Status(Vec<Status>),
+    /// Repository root path or directory name.
+    Repo(Vec<String>),
+    Slug(Vec<String>),
+    Branch(Vec<String>),
+    Label(Vec<String>),
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected key=value, got {s:?}"))?;
+        let values: Vec<String> = value.split(',').map(|v| v.trim().to_string()).collect();
+        match key.trim() {
+            "status" => values
+                .iter()
+                .map(|v| v.parse())
+                .collect::<Result<_, _>>()
+                .map(Filter::Status),
+            "repo" => Ok(Filter::Repo(values)),
+            "slug" => Ok(Filter::Slug(values)),
+            "branch" => Ok(Filter::Branch(values)),
+            "label" => Ok(Filter::Label(values)),
+            other => Err(format!(
+                "unknown filter key {other:?} (expected status, repo, slug, branch or label)"
+            )),
+        }
+    }
+}
+
+impl Filter {
+    pub fn matches(&self, session: &Session) -> bool {
+        match self {
+            Filter::Status(statuses) => statuses.contains(&session.status),
+            Filter::Repo(repos) => session.git_repo.as_ref().is_some_and(|repo| {
+                let name = slug_from_cwd(repo);
+                repos.iter().any(|r| r == repo || *r == name)
+            }),
+            Filter::Slug(slugs) => {
+                // Ignore the " [abcd]" disambiguation suffix
+                let base = base_slug(session);
+                slugs.iter().any(|s| s == base || *s == session.slug)
+            }
+            Filter::Branch(branches) => session
+                .git_branch
+                .as_ref()
+                .is_some_and(|b| branches.contains(b)),
+            Filter::Label(labels) => session.labels.iter().any(|l| labels.contains(l)),
+        }
+    }
+}
+
+/// True if the session passes every filter.
+pub fn matches_all(filters: &[Filter], session: &Session) -> bool {
+    filters.iter().all(|f| f.matches(session))
+}
@@ -0,0 +1,220 @@
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::session::{SessionFlag, SlugStyle, Status};
+
+/// Settings loaded from `~/.config/bottycall/config.toml`.
+/// Every field has a default, so a missing file or section is fine.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Daemon socket path; `--socket` / `BOTTYCALL_SOCKET` take precedence.
+    pub socket: Option<PathBuf>,
+    pub daemon: DaemonConfig,
+    pub tui: TuiConfig,
+    pub tmux: TmuxConfig,
+    pub notify: NotifyConfig,
+    /// Webhooks and commands fed with status transitions (`[[sinks]]` tables).
+    pub sinks: Vec<SinkConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DaemonConfig {
+    /// Seconds between tmux/process scans.
+    pub poll_interval_secs: u64,
+    /// Seconds between pane captures for live token counts.
+    pub token_poll_interval_secs: u64,
+    /// Number of buffered updates before slow subscribers get a fresh snapshot.
+    pub broadcast_capacity: usize,
+    /// Process name identifying a Claude Code session.
+    pub process_name: String,
+    /// Directories prepended to PATH so tmux and git are found under launchd.
+    pub path_prefix: Vec<String>,
+    /// Hook events kept per session for the `history` query.
+    pub history_len: usize,
+    /// How session slugs are derived: "smart", "repo-branch" or "directory".
+    pub slug_style: SlugStyle,
+    /// Flag a working session as stalled after this many seconds without a hook event
+    /// or pane change (0 = never).
+    pub stalled_after_secs: u64,
+    /// Flag an idle session as forgotten after this many seconds (0 = never).
+    pub forgotten_after_secs: u64,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval_secs: 5,
+            token_poll_interval_secs: 2,
+            broadcast_capacity: 256,
+            process_name: "claude".to_string(),
+            path_prefix: vec!["/opt/homebrew/bin".to_string(), "/usr/local/bin".to_string()],
+            history_len: 50,
+            slug_style: SlugStyle::Smart,
+            stalled_after_secs: 20 * 60,
+            forgotten_after_secs: 4 * 60 * 60,
+        }
+    }
+}
+
+/// TUI settings. Colours are ratatui colour names ("yellow", "dark_gray") or hex ("#ff8800").
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TuiConfig {
+    pub accent: String,
+    pub working: String,
+    pub attention: String,
+    pub idle: String,
+    pub muted: String,
+    /// Seconds after which a running tool is flagged as suspiciously long.
+    pub long_tool_secs: u64,
+}
+
+impl Default for TuiConfig {
+    fn default() -> Self {
+        Self {
+            accent: "cyan".to_string(),
+            working: "yellow".to_string(),
+            attention: "magenta".to_string(),
+            idle: "green".to_string(),
+            muted: "dark_gray".to_string(),
+            long_tool_secs: 300,
+        }
+    }
+}
+
+/// `bottycall tmux-status` settings.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TmuxConfig {
+    /// Summary format; see `bottycall tmux-status --help`.
+    pub format: String,
+    /// Seconds a cached snapshot is reused before asking the daemon again.
+    pub cache_secs: u64,
+}
+
+impl Default for TmuxConfig {
+    fn default() -> Self {
+        Self {
+            format: "{attention?#[fg=magenta]💬} {working?#[fg=yellow]⚡} {idle?#[fg=green]✓}#[default]"
+                .to_string(),
+            cache_secs: 2,
+        }
+    }
+}
+
+/// Desktop notifications fired by the daemon on status transitions.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifyConfig {
+    pub enabled: bool,
+    /// Notify when a session starts waiting for permission or input.
+    pub on_attention: bool,
+    /// Notify when a working session goes idle.
+    pub on_idle_after_working: bool,
+    /// Notify when a session is flagged as stalled.
+    pub on_stalled: bool,
+    /// Notify when a session is flagged as forgotten.
+    pub on_forgotten: bool,
+    /// Use the freedesktop `org.freedesktop.Notifications` service (via `gdbus`).
+    pub dbus: bool,
+    /// Fallback command when D-Bus is unavailable; `{title}` and `{body}` are substituted,
+    /// e.g. `["notify-send", "{title}", "{body}"]`.
+    pub command: Option<Vec<String>>,
+    /// Minimum seconds between notifications for the same session.
+    pub min_interval_secs: u64,
+    /// Repositories to never notify about, by root path or directory name.
+    pub mute_repos: Vec<String>,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            on_attention: true,
+            on_idle_after_working: false,
+            on_stalled: true,
+            on_forgotten: false,
+            dbus: true,
+            command: None,
+            min_interval_secs: 30,
+            mute_repos: Vec::new(),
+        }
+    }
+}
+
+/// One transition sink: either POST JSON to `url` or run `command` with the JSON on stdin.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SinkConfig {
+    pub url: Option<String>,
+    pub command: Option<Vec<String>>,
+    /// Only fire for transitions out of these statuses (empty = any).
+    pub from: Vec<Status>,
+    /// Only fire for transitions into these statuses (empty = any).
+    pub to: Vec<Status>,
+    /// Also fire when a session gets one of these flags ("Stalled", "Forgotten").
+    pub flags: Vec<SessionFlag>,
+    pub timeout_secs: u64,
+    /// Extra attempts after a failed delivery.
+    pub retries: u32,
+}
+
+impl Default for SinkConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            command: None,
+            from: Vec::new(),
+            to: Vec::new(),
+            flags: Vec::new(),
+            timeout_secs: 5,
+            retries: 2,
+        }
+    }
+}
+
+/// Default config location: `$XDG_CONFIG_HOME/bottycall/config.toml`, falling back to `~/.config`.
+pub fn default_config_path() -> PathBuf {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            let home = std::env::var_os("HOME").unwrap_or_default();
+            PathBuf::from(home).join(".config")
+        });
+    base.join("bottycall").join("config.toml")
+}
+
+/// Load the config file. A missing file yields defaults; a malformed one is an error.
+pub fn load(path: &Path) -> anyhow::Result<Config> {
+    let config = match std::fs::read_to_string(path) {
+        Ok(data) => toml::from_str::<Config>(&data)
+            .map_err(|e| anyhow::anyhow!("invalid config {}: {e}", path.display()))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+        Err(e) => return Err(anyhow::anyhow!("failed to read {}: {e}", path.display())),
+    };
+    config
+        .validate()
+        .map_err(|e| anyhow::anyhow!("invalid config {}: {e}", path.display()))?;
+    Ok(config)
+}
+
+impl Config {
+    /// Checks that serde can't express, e.g. a sink needing exactly one destination.
+    fn validate(&self) -> anyhow::Result<()> {
+        for (i, sink) in self.sinks.iter().enumerate() {
+            match (&sink.url, &sink.command) {
+                (Some(_), Some(_)) => anyhow::bail!("sinks[{i}] sets both url and command"),
+                (None, None) => anyhow::bail!("sinks[{i}] needs a url or a command"),
+                (None, Some(command)) if command.is_empty() => {
+                    anyhow::bail!("sinks[{i}] has an empty command")
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+use std::path::Path;
+
+use clap::Subcommand;
+
+use crate::client::Connection;
+use crate::protocol::{ClientHello, ControlCommand, PromptAnswer, ServerMessage};
+use crate::session::Status;
+
+/// `bottycall ctl` operations. Sessions may be named by id, unique id prefix or slug.
+#[derive(Subcommand)]
+pub enum CtlCommand {
+    /// Check that the daemon is responding
+    Ping,
+    /// Show one session
+    Get { session: String },
+    /// Drop a session from the daemon
+    Forget { session: String },
+    /// Override a session's status (idle, working, attention)
+    SetStatus { session: String, status: Status },
+    /// Set a session's display name; omit the name to go back to the directory name
+    Rename {
+        session: String,
+        name: Option<String>,
+        /// Apply to every session in the same working directory, now and later
+        #[arg(long)]
+        dir: bool,
+    },
+    /// Replace a session's labels; give none to clear them
+    Label {
+        session: String,
+        labels: Vec<String>,
+        /// Apply to every session in the same working directory, now and later
+        #[arg(long)]
+        dir: bool,
+    },
+    /// Answer the permission prompt showing in a session's pane (approve, always, deny)
+    Answer {
+        session: String,
+        answer: PromptAnswer,
+    },
+    /// Stop the daemon
+    Shutdown,
+}
+
+impl From<CtlCommand> for ControlCommand {
+    fn from(cmd: CtlCommand) -> Self {
+        match cmd {
+            CtlCommand::Ping => ControlCommand::Ping,
+            CtlCommand::Get { session } => ControlCommand::GetSession { session },
+            CtlCommand::Forget { session } => ControlCommand::ForgetSession { session },
+            CtlCommand::SetStatus { session, status } => {
+                ControlCommand::SetStatus { session, status }
+            }
+            CtlCommand::Rename { session, name, dir } => {
+                ControlCommand::RenameSession { session, name, dir }
+            }
+            CtlCommand::Label {
+                session,
+                labels,
+                dir,
+            } => ControlCommand::SetLabels {
+                session,
+                labels,
+                dir,
+            },
+            CtlCommand::Answer { session, answer } => {
+                ControlCommand::AnswerPrompt { session, answer }
+            }
+            CtlCommand::Shutdown => ControlCommand::Shutdown,
+        }
+    }
+}
+
+/// Send one control command and print the daemon's reply.
+pub fn run(socket: &Path, cmd: CtlCommand, json: bool) {
+    if let Err(e) = run_inner(socket, cmd, json) {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
+}
+
+fn run_inner(socket: &Path, cmd: CtlCommand, json: bool) -> anyhow::Result<()> {
+    let reply = request(socket, cmd.into())?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&reply)?);
+        return Ok(());
+    }
+
+    match reply {
+        ServerMessage::Pong { pid } => println!("daemon is running (pid {pid})"),
+        ServerMessage::Session { session } => {
+            println!("{}", serde_json::to_string_pretty(&session)?)
+        }
+        ServerMessage::Forgotten { session_id } => println!("forgot {session_id}"),
+        ServerMessage::ShuttingDown => println!("daemon is shutting down"),
+        other => anyhow::bail!("unexpected reply: {other:?}"),
+    }
+    Ok(())
+}
+
+/// Send a control command on a fresh connection; daemon-side failures become errors.
+pub fn request(socket: &Path, cmd: ControlCommand) -> anyhow::Result<ServerMessage> {
+    let mut conn = Connection::open(socket, ClientHello::Command(cmd))?;
+    match conn.recv()? {
+        Some(ServerMessage::Error { message }) => anyhow::bail!(message),
+        Some(reply) => Ok(reply),
+        None => anyhow::bail!("daemon closed the connection without replying"),
+    }
+}
@@ -0,0 +1,201 @@
+use std::sync::Arc;
+
+use tokio::sync::{Mutex, Notify, broadcast};
+
+use crate::mux::{Key, MuxKind};
+use crate::protocol::{ControlCommand, PromptAnswer, ServerMessage};
+use crate::session::Status;
+
+use super::pane::PaneSnapshot;
+use super::state::SessionMap;
+
+/// Execute one control command and return the reply for the client.
+pub async fn handle_command(
+    cmd: ControlCommand,
+    state: &Arc<Mutex<SessionMap>>,
+    tx: &broadcast::Sender<ServerMessage>,
+    shutdown: &Notify,
+) -> ServerMessage {
+    match run(cmd, state, tx, shutdown).await {
+        Ok(reply) => reply,
+        Err(message) => ServerMessage::Error { message },
+    }
+}
+
+async fn run(
+    cmd: ControlCommand,
+    state: &Arc<Mutex<SessionMap>>,
+    tx: &broadcast::Sender<ServerMessage>,
+    shutdown: &Notify,
+) -> Result<ServerMessage, String> {
+    match cmd {
+        ControlCommand::Ping => Ok(ServerMessage::Pong {
+            pid: std::process::id(),
+        }),
+        ControlCommand::GetSession { session } => {
+            let map = state.lock().await;
+            let id = map.resolve(&session)?;
+            let session = map.get(&id).cloned().ok_or("session vanished")?;
+            Ok(ServerMessage::Session {
+                session: Box::new(session),
+            })
+        }
+        ControlCommand::ForgetSession { session } => {
+            let mut map = state.lock().await;
+            let id = map.resolve(&session)?;
+            for msg in map.remove(&id) {
+                let _ = tx.send(msg);
+            }
+            Ok(ServerMessage::Forgotten { session_id: id })
+        }
+        ControlCommand::SetStatus { session, status } => {
+            let mut map = state.lock().await;
+            let id = map.resolve(&session)?;
+            let update = map.set_status(&id, status).ok_or("session vanished")?;
+            Ok(broadcast_session(tx, &id, vec![update]))
+        }
+        ControlCommand::RenameSession { session, name, dir } => {
+            let name = name.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+            let mut map = state.lock().await;
+            let id = map.resolve(&session)?;
+            let updates = map.rename(&id, name, dir)?;
+            Ok(broadcast_session(tx, &id, updates))
+        }
+        ControlCommand::SetLabels {
+            session,
+            labels,
+            dir,
+        } => {
+            let mut map = state.lock().await;
+            let id = map.resolve(&session)?;
+            let updates = map.set_labels(&id, normalize_labels(labels), dir)?;
+            Ok(broadcast_session(tx, &id, updates))
+        }
+        ControlCommand::AnswerPrompt { session, answer } => {
+            let (id, mux, pane) = {
+                let map = state.lock().await;
+                let id = map.resolve(&session)?;
+                let session = map.get(&id).ok_or("session vanished")?;
+                let (mux, pane) = session.pane().ok_or("session has no pane to answer in")?;
+                (id, mux, pane.to_string())
+            };
+            tokio::task::spawn_blocking(move || answer_prompt(mux, &pane, answer))
+                .await
+                .map_err(|e| e.to_string())??;
+            // The hooks that follow the answer update the status as usual
+            let map = state.lock().await;
+            let session = map.get(&id).cloned().ok_or("session vanished")?;
+            Ok(ServerMessage::Session {
+                session: Box::new(session),
+            })
+        }
+        ControlCommand::SendPrompt { session, text } => {
+            let text = text.trim_end().to_string();
+            if text.trim().is_empty() {
+                return Err("nothing to send".to_string());
+            }
+            let (id, mux, pane) = {
+                let map = state.lock().await;
+                let id = map.resolve(&session)?;
+                let session = map.get(&id).ok_or("session vanished")?;
+                if session.status == Status::Attention {
+                    return Err(format!("{} is waiting for an answer", session.slug));
+                }
+                let (mux, pane) = session.pane().ok_or("session has no pane to type into")?;
+                (id, mux, pane.to_string())
+            };
+            tokio::task::spawn_blocking(move || send_prompt(mux, &pane, &text))
+                .await
+                .map_err(|e| e.to_string())??;
+            let map = state.lock().await;
+            let session = map.get(&id).cloned().ok_or("session vanished")?;
+            Ok(ServerMessage::Session {
+                session: Box::new(session),
+            })
+        }
+        ControlCommand::Shutdown => {
+            shutdown.notify_one();
+            Ok(ServerMessage::ShuttingDown)
+        }
+    }
+}
+
+/// Broadcast `Update`s to subscribers and echo the requested session back to the caller.
+fn broadcast_session(
+    tx: &broadcast::Sender<ServerMessage>,
+    session_id: &str,
+    updates: Vec<ServerMessage>,
+) -> ServerMessage {
+    let mut reply = ServerMessage::Error {
+        message: "session vanished".to_string(),
+    };
+    for update in updates {
+        if let ServerMessage::Update { session } = &update
+            && session.session_id == session_id
+        {
+            reply = ServerMessage::Session {
+                session: session.clone(),
+            };
+        }
+        let _ = tx.send(update);
+    }
+    reply
+}
+
+/// Type the key for `answer` into the pane, after checking that the pane really shows a
+/// permission dialog offering it. The status alone isn't enough: the user may already have
+/// answered in the pane, and a stray digit would land in Claude's input box instead.
+fn answer_prompt(mux: MuxKind, pane: &str, answer: PromptAnswer) -> Result<(), String> {
+    let backend = mux.backend();
+    let content = backend
+        .capture_pane(pane)
+        .ok_or_else(|| format!("can't read {} pane {pane}", mux.label()))?;
+    let snapshot = PaneSnapshot::parse(&content);
+    if !snapshot.permission_prompt {
+        return Err("no permission prompt is showing".to_string());
+    }
+    let key = match answer {
+        PromptAnswer::Approve => Key::Text("1"),
+        PromptAnswer::ApproveAlways if snapshot.always_allow => Key::Text("2"),
+        PromptAnswer::ApproveAlways => {
+            return Err("this prompt has no \"don't ask again\" choice".to_string());
+        }
+        PromptAnswer::Deny => Key::Escape,
+    };
+    backend.send_keys(pane, &[key]).map_err(|e| e.to_string())
+}
+
+/// Type `text` into the pane and press Enter. Text with newlines goes in as one paste, since
+/// typed newlines would submit each line separately.
+fn send_prompt(mux: MuxKind, pane: &str, text: &str) -> Result<(), String> {
+    let backend = mux.backend();
+    // Status can lag behind the pane; never let the text answer a dialog
+    if let Some(content) = backend.capture_pane(pane)
+        && PaneSnapshot::parse(&content).permission_prompt
+    {
+        return Err("a permission prompt is showing".to_string());
+    }
+    let typed = if text.contains('\n') {
+        backend.paste(pane, text)
+    } else {
+        backend.send_keys(pane, &[Key::Text(text)])
+    };
+    typed
+        .and_then(|()| backend.send_keys(pane, &[Key::Enter]))
+        .map_err(|e| e.to_string())
+}
+
+/// Trim labels, splitting on commas and whitespace, and drop empties and duplicates.
+fn normalize_labels(labels: Vec<String>) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for label in labels
+        .iter()
+        .flat_map(|l| l.split(|c: char| c == ',' || c.is_whitespace()))
+        .filter(|l| !l.is_empty())
+    {
+        if !out.iter().any(|l| l == label) {
+            out.push(label.to_string());
+        }
+    }
+    out
+}
@@ -1,11 +1,13 @@
 use std::sync::Arc;
 
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::unix::OwnedWriteHalf;
 use tokio::net::{UnixListener, UnixStream};
-use tokio::sync::{Mutex, broadcast};
+use tokio::sync::{Mutex, Notify, broadcast};
 
-use crate::protocol::{ClientHello, ServerMessage};
+use crate::protocol::{ClientHello, Hello, PROTOCOL_VERSION, ServerMessage};
 
+use super::control::handle_command;
 use super::state::SessionMap;
 
 /// Accept connections on the Unix socket and dispatch them.
@@ -13,14 +15,16 @@ pub async fn accept_loop(
     listener: UnixListener,
     state: Arc<Mutex<SessionMap>>,
     tx: broadcast::Sender<ServerMessage>,
+    shutdown: Arc<Notify>,
 ) {
     loop {
         match listener.accept().await {
             Ok((stream, _addr)) => {
                 let state = Arc::clone(&state);
                 let tx = tx.clone();
+                let shutdown = Arc::clone(&shutdown);
                 tokio::spawn(async move {
-                    if let Err(e) = handle_connection(stream, state, tx).await {
+                    if let Err(e) = handle_connection(stream, state, tx, shutdown).await {
                         eprintln!("connection error: {e}");
                     }
                 });
@@ -36,8 +40,9 @@ async fn handle_connection(
     stream: UnixStream,
     state: Arc<Mutex<SessionMap>>,
     tx: broadcast::Sender<ServerMessage>,
+    shutdown: Arc<Notify>,
 ) -> anyhow::Result<()> {
-    let (reader, writer) = stream.into_split();
+    let (reader, mut writer) = stream.into_split();
     let mut reader = BufReader::new(reader);
     let mut line = String::new();
 
@@ -48,9 +53,25 @@ async fn handle_connection(
         return Ok(());
     }
 
-    let hello: ClientHello = serde_json::from_str(line)?;
+    let hello: Hello = match serde_json::from_str(line) {
+        Ok(hello) => hello,
+        Err(e) => {
+            // Most likely a newer client using a request this daemon doesn't know
+            let message =
+                format!("unsupported request ({e}); daemon speaks protocol v{PROTOCOL_VERSION}");
+            return reject(&mut writer, message).await;
+        }
+    };
+
+    if let Err(message) = hello.check() {
+        return reject(&mut writer, message).await;
+    }
+    if hello.version.is_some() {
+        // Hook reporters don't wait for the reply, so a failed write is expected there
+        let _ = send(&mut writer, &ServerMessage::welcome()).await;
+    }
 
-    match hello {
+    match hello.request {
         ClientHello::Report(report) => {
             let mut map = state.lock().await;
             for msg in map.apply_report(&report) {
@@ -60,13 +81,40 @@ async fn handle_connection(
         ClientHello::Subscribe => {
             handle_subscriber(writer, state, tx).await?;
         }
+        ClientHello::History { session_id } => {
+            let events = state.lock().await.history(&session_id);
+            send(&mut writer, &ServerMessage::History { session_id, events }).await?;
+        }
+        ClientHello::Ping => {
+            let pid = std::process::id();
+            send(&mut writer, &ServerMessage::Pong { pid }).await?;
+        }
+        ClientHello::Command(cmd) => {
+            let reply = handle_command(cmd, &state, &tx, &shutdown).await;
+            send(&mut writer, &reply).await?;
+        }
     }
 
     Ok(())
 }
 
+/// Refuse a client with an `Error` and close the connection.
+async fn reject(writer: &mut OwnedWriteHalf, message: String) -> anyhow::Result<()> {
+    eprintln!("rejecting client: {message}");
+    let _ = send(writer, &ServerMessage::Error { message }).await;
+    Ok(())
+}
+
+/// Write one message as an NDJSON line.
+async fn send(writer: &mut OwnedWriteHalf, msg: &ServerMessage) -> anyhow::Result<()> {
+    let mut data = serde_json::to_string(msg)?;
+    data.push('\n');
+    writer.write_all(data.as_bytes()).await?;
+    Ok(())
+}
+
 async fn handle_subscriber(
-    mut writer: tokio::net::unix::OwnedWriteHalf,
+    mut writer: OwnedWriteHalf,
     state: Arc<Mutex<SessionMap>>,
     tx: broadcast::Sender<ServerMessage>,
 ) -> anyhow::Result<()> {
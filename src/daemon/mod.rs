@@ -1,51 +1,161 @@
+mod control;
 mod listener;
+mod names;
+mod notify;
+mod pane;
+mod persist;
 mod poller;
+mod sinks;
 pub mod state;
+mod timers;
+mod transitions;
 
+use std::fs::{DirBuilder, File, OpenOptions};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use tokio::net::UnixListener;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
 use tokio::signal;
-use tokio::sync::{Mutex, broadcast};
+use tokio::sync::{Mutex, Notify, broadcast};
+use tokio::time::{Duration, timeout};
 
-use crate::protocol::{SOCKET_PATH, ServerMessage};
+use crate::config::Config;
+use crate::procs;
+use crate::protocol::{ServerMessage, current_uid};
 
 use state::SessionMap;
 
-pub async fn run() {
-    // Clean up stale socket
-    let _ = std::fs::remove_file(SOCKET_PATH);
+pub async fn run(socket: &Path, config: Config) {
+    let Config {
+        daemon: config,
+        notify: notify_config,
+        sinks: sink_configs,
+        ..
+    } = config;
 
-    let listener = UnixListener::bind(SOCKET_PATH).expect("failed to bind socket");
-    eprintln!("bottycall daemon listening on {SOCKET_PATH}");
+    if let Err(e) = prepare_socket_dir(socket) {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
+
+    // Held until the daemon exits, so two daemons starting together can't both find the
+    // socket stale and have one unlink the socket the other just bound
+    let _lock = match lock_socket(socket) {
+        Ok(Some(lock)) => lock,
+        Ok(None) => {
+            eprintln!(
+                "error: another bottycall daemon is running or starting on {}",
+                socket.display()
+            );
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("error: failed to lock {}: {e}", lock_path(socket).display());
+            std::process::exit(1);
+        }
+    };
+
+    // Never steal the socket from a running daemon; only clean up a stale one
+    match probe_existing(socket).await {
+        Probe::Missing => {}
+        Probe::Stale => {
+            eprintln!("removing stale socket {}", socket.display());
+            let _ = std::fs::remove_file(socket);
+        }
+        Probe::Alive(pid) => {
+            let who = pid.map_or_else(|| "another process".to_string(), |p| format!("pid {p}"));
+            eprintln!(
+                "error: a bottycall daemon ({who}) is already listening on {}",
+                socket.display()
+            );
+            std::process::exit(1);
+        }
+    }
+
+    let listener = bind_private(socket).expect("failed to bind socket");
+    eprintln!("bottycall daemon listening on {}", socket.display());
+
+    let state_path = persist::state_path();
+    let mut map = SessionMap::new(
+        config.history_len,
+        persist::load_names(&persist::names_path(&state_path)),
+        config.slug_style,
+    );
+    map.restore(persist::load(&state_path));
+
+    let state = Arc::new(Mutex::new(map));
+    let (tx, _rx) = broadcast::channel::<ServerMessage>(config.broadcast_capacity.max(1));
 
-    let state = Arc::new(Mutex::new(SessionMap::new()));
-    let (tx, _rx) = broadcast::channel::<ServerMessage>(256);
+    // Reconcile restored sessions and discover new ones before accepting connections
+    let processes = procs::detect();
+    poller::reconcile_restored(&state, &tx, &processes, &config.process_name).await;
 
-    // Discover existing sessions before accepting connections
-    poller::poll_once(&state, &tx).await;
+    // Signalled by the `shutdown` control command
+    let shutdown = Arc::new(Notify::new());
 
     // Spawn the socket accept loop
     let accept_state = Arc::clone(&state);
     let accept_tx = tx.clone();
+    let accept_shutdown = Arc::clone(&shutdown);
     tokio::spawn(async move {
-        listener::accept_loop(listener, accept_state, accept_tx).await;
+        listener::accept_loop(listener, accept_state, accept_tx, accept_shutdown).await;
     });
 
     // Spawn the tmux poller
     let poll_state = Arc::clone(&state);
     let poll_tx = tx.clone();
+    let poll_every = Duration::from_secs(config.poll_interval_secs.max(1));
+    let process_name = config.process_name.clone();
     tokio::spawn(async move {
-        poller::poll_loop(poll_state, poll_tx).await;
+        poller::poll_loop(poll_state, poll_tx, poll_every, processes, process_name).await;
     });
 
     // Spawn the live token poller
     let token_state = Arc::clone(&state);
     let token_tx = tx.clone();
+    let token_every = Duration::from_secs(config.token_poll_interval_secs.max(1));
     tokio::spawn(async move {
-        poller::token_poll_loop(token_state, token_tx).await;
+        poller::token_poll_loop(token_state, token_tx, token_every).await;
     });
 
+    // Spawn the stalled / forgotten timers
+    let timer_state = Arc::clone(&state);
+    let timer_tx = tx.clone();
+    let (stalled_after, forgotten_after) =
+        (config.stalled_after_secs, config.forgotten_after_secs);
+    tokio::spawn(async move {
+        timers::flag_loop(timer_state, timer_tx, stalled_after, forgotten_after).await;
+    });
+
+    // Spawn the state snapshot writer
+    let save_state = Arc::clone(&state);
+    let save_tx = tx.clone();
+    let save_path = state_path.clone();
+    tokio::spawn(async move {
+        persist::save_loop(save_state, save_tx, save_path).await;
+    });
+
+    // Spawn the desktop notifier
+    if notify_config.enabled {
+        let notify_state = Arc::clone(&state);
+        let notify_tx = tx.clone();
+        tokio::spawn(async move {
+            notify::notify_loop(notify_state, notify_tx, notify_config).await;
+        });
+    }
+
+    // Spawn the webhook / command sinks
+    if !sink_configs.is_empty() {
+        let sink_state = Arc::clone(&state);
+        let sink_tx = tx.clone();
+        tokio::spawn(async move {
+            sinks::sink_loop(sink_state, sink_tx, sink_configs).await;
+        });
+    }
+
     // Wait for shutdown signal
     tokio::select! {
         _ = signal::ctrl_c() => {
@@ -58,8 +168,112 @@ pub async fn run() {
         } => {
             eprintln!("\nreceived SIGTERM, shutting down...");
         }
+        _ = shutdown.notified() => {
+            eprintln!("shutdown requested over the socket, shutting down...");
+        }
     }
 
     // Cleanup
-    let _ = std::fs::remove_file(SOCKET_PATH);
+    persist::save(&state, &state_path).await;
+    let _ = std::fs::remove_file(socket);
+}
+
+/// Bind the socket with only the owning user allowed to report to or subscribe on the
+/// daemon. The mode comes from the umask at `bind` time, so there is no window in which
+/// the socket is open to everyone, as there would be with a chmod afterwards.
+fn bind_private(socket: &Path) -> std::io::Result<UnixListener> {
+    // SAFETY: umask has no memory-safety requirements. It is process-wide, but nothing
+    // else creates files while the daemon is still starting up.
+    let previous = unsafe { libc::umask(0o177) };
+    let listener = UnixListener::bind(socket);
+    // SAFETY: as above; restores the mask saved before binding.
+    unsafe { libc::umask(previous) };
+    listener
+}
+
+/// Create the socket's directory, mode 0700 where missing, and make sure no other user
+/// controls it: it must belong to us or to root, as /tmp and runtime dirs do.
+fn prepare_socket_dir(socket: &Path) -> anyhow::Result<()> {
+    let Some(dir) = socket.parent().filter(|d| !d.as_os_str().is_empty()) else {
+        return Ok(());
+    };
+    DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+        .map_err(|e| anyhow::anyhow!("failed to create {}: {e}", dir.display()))?;
+    let meta = std::fs::symlink_metadata(dir)?;
+    anyhow::ensure!(meta.is_dir(), "{} is not a directory", dir.display());
+    anyhow::ensure!(
+        meta.uid() == current_uid() || meta.uid() == 0,
+        "socket directory {} is owned by uid {}, not by you",
+        dir.display(),
+        meta.uid()
+    );
+    Ok(())
+}
+
+/// Lock file guarding the socket: `<socket>.lock`.
+fn lock_path(socket: &Path) -> PathBuf {
+    let mut path = socket.as_os_str().to_owned();
+    path.push(".lock");
+    PathBuf::from(path)
+}
+
+/// Take an exclusive lock on the socket's lock file, or `None` if another daemon holds it.
+/// The lock is released when the returned file is closed. The file itself is never
+/// removed, since unlinking it would let a third daemon lock a fresh file alongside.
+fn lock_socket(socket: &Path) -> std::io::Result<Option<File>> {
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .mode(0o600)
+        .open(lock_path(socket))?;
+    // SAFETY: flock only acts on the descriptor, which `file` keeps open for the call.
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return Ok(Some(file));
+    }
+    let err = std::io::Error::last_os_error();
+    if err.raw_os_error() == Some(libc::EWOULDBLOCK) {
+        Ok(None)
+    } else {
+        Err(err)
+    }
+}
+
+enum Probe {
+    /// No socket file exists.
+    Missing,
+    /// A socket file exists but nothing accepts connections on it.
+    Stale,
+    /// Something is listening; the pid is known if it answered our ping.
+    Alive(Option<u32>),
+}
+
+/// Check whether a daemon is already serving the socket by exchanging a ping.
+async fn probe_existing(socket: &Path) -> Probe {
+    if !socket.exists() {
+        return Probe::Missing;
+    }
+
+    let Ok(Ok(stream)) = timeout(Duration::from_secs(1), UnixStream::connect(socket)).await else {
+        return Probe::Stale;
+    };
+
+    // Unversioned on purpose: every daemon generation answers a bare ping directly
+    let (reader, mut writer) = stream.into_split();
+    if writer.write_all(b"{\"type\":\"ping\"}\n").await.is_err() {
+        return Probe::Alive(None);
+    }
+
+    let mut line = String::new();
+    let mut reader = BufReader::new(reader);
+    match timeout(Duration::from_secs(1), reader.read_line(&mut line)).await {
+        Ok(Ok(_)) => match serde_json::from_str(line.trim()) {
+            Ok(ServerMessage::Pong { pid }) => Probe::Alive(Some(pid)),
+            _ => Probe::Alive(None),
+        },
+        _ => Probe::Alive(None),
+    }
 }
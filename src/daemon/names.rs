@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::session::Session;
+
+/// A user-chosen name and labels.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Naming {
+    #[serde(default)]
+    pub name: Option<String>,
+    /// `None` inherits the directory's labels; on a session, `Some(vec![])` clears them.
+    #[serde(default)]
+    pub labels: Option<Vec<String>>,
+}
+
+impl Naming {
+    fn is_empty(&self) -> bool {
+        self.name.is_none() && self.labels.is_none()
+    }
+}
+
+/// What a naming is attached to.
+pub enum NameKey {
+    Session(String),
+    /// A working directory; every session started there inherits the naming.
+    Dir(String),
+}
+
+/// Names and labels set by the user, persisted across daemon restarts.
+/// A session's own naming takes precedence over its directory's, field by field.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NameStore {
+    #[serde(default)]
+    sessions: HashMap<String, Naming>,
+    #[serde(default)]
+    dirs: HashMap<String, Naming>,
+}
+
+impl NameStore {
+    /// Modify the naming stored under `key`, dropping it once nothing is left.
+    pub fn update(&mut self, key: NameKey, f: impl FnOnce(&mut Naming)) {
+        let (map, key) = match key {
+            NameKey::Session(id) => (&mut self.sessions, id),
+            NameKey::Dir(cwd) => (&mut self.dirs, cwd),
+        };
+        let naming = map.entry(key.clone()).or_default();
+        f(naming);
+        if naming.is_empty() {
+            map.remove(&key);
+        }
+    }
+
+    /// Forget the per-session naming of a session that has gone away.
+    pub fn forget_session(&mut self, session_id: &str) {
+        self.sessions.remove(session_id);
+    }
+
+    /// Drop per-session namings whose session isn't tracked any more, e.g. sessions that
+    /// ended while the daemon was down.
+    pub fn retain_sessions(&mut self, keep: impl Fn(&str) -> bool) {
+        self.sessions.retain(|id, _| keep(id));
+    }
+
+    /// The effective naming for a session.
+    pub fn lookup(&self, session: &Session) -> Naming {
+        let own = self.sessions.get(&session.session_id);
+        let dir = session.cwd.as_ref().and_then(|cwd| self.dirs.get(cwd));
+        let name = own
+            .and_then(|n| n.name.clone())
+            .or_else(|| dir.and_then(|n| n.name.clone()));
+        let labels = own
+            .and_then(|n| n.labels.clone())
+            .or_else(|| dir.and_then(|n| n.labels.clone()));
+        Naming { name, labels }
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use tokio::process::Command;
+use tokio::sync::{Mutex, broadcast};
+use tokio::time::{Duration, timeout};
+
+use crate::config::NotifyConfig;
+use crate::protocol::ServerMessage;
+use crate::session::{Session, SessionFlag, Status, format_elapsed, slug_from_cwd};
+
+use super::state::SessionMap;
+use super::transitions::{Transition, watch_transitions};
+
+/// Give up on a notification helper that hasn't finished by then.
+const SEND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Fire desktop notifications when sessions start needing attention (and optionally
+/// when they finish working).
+pub async fn notify_loop(
+    state: Arc<Mutex<SessionMap>>,
+    tx: broadcast::Sender<ServerMessage>,
+    config: NotifyConfig,
+) {
+    let mut last_sent: HashMap<String, DateTime<Utc>> = HashMap::new();
+
+    watch_transitions(&state, &tx, |transition| {
+        if !should_notify(&config, &transition) {
+            return;
+        }
+
+        // Rate limit per session so a flapping prompt doesn't spam the desktop. Entries
+        // past the interval no longer matter, which also forgets removed sessions.
+        let now = Utc::now();
+        let min_interval = config.min_interval_secs as i64;
+        last_sent.retain(|_, prev| (now - *prev).num_seconds() < min_interval);
+        let id = &transition.session.session_id;
+        if last_sent.contains_key(id) {
+            return;
+        }
+        last_sent.insert(id.clone(), now);
+
+        let (title, body) = render(&transition);
+        let config = config.clone();
+        // Never hold up the broadcast receiver on a slow notification helper
+        tokio::spawn(async move {
+            send(&config, &title, &body).await;
+        });
+    })
+    .await;
+}
+
+fn should_notify(config: &NotifyConfig, t: &Transition) -> bool {
+    let wanted = match (t.flagged, t.to) {
+        (Some(SessionFlag::Stalled), _) => config.on_stalled,
+        (Some(SessionFlag::Forgotten), _) => config.on_forgotten,
+        _ if !t.status_changed() => false,
+        (None, Status::Attention) => config.on_attention,
+        (None, Status::Idle) => config.on_idle_after_working && t.from == Some(Status::Working),
+        (None, Status::Working) => false,
+    };
+    wanted && !is_muted(config, &t.session)
+}
+
+/// A repo is muted if a rule matches its root path or its directory name.
+fn is_muted(config: &NotifyConfig, session: &Session) -> bool {
+    let Some(repo) = &session.git_repo else {
+        return false;
+    };
+    let name = slug_from_cwd(repo);
+    config
+        .mute_repos
+        .iter()
+        .any(|rule| rule == repo || *rule == name)
+}
+
+fn render(t: &Transition) -> (String, String) {
+    let title = format!("{} {}", t.to.icon(), t.session.slug);
+    let now = Utc::now();
+    let body = match (t.flagged, t.to) {
+        (Some(SessionFlag::Stalled), _) => {
+            "Working without progress — Claude may be stuck".to_string()
+        }
+        (Some(SessionFlag::Forgotten), _) => format!(
+            "Idle for {}",
+            format_elapsed(t.session.status_since.unwrap_or(t.session.last_activity), now)
+        ),
+        (None, Status::Attention) => t
+            .session
+            .last_message
+            .clone()
+            .unwrap_or_else(|| "Claude needs your attention".to_string()),
+        (None, _) => "Claude finished working".to_string(),
+    };
+    (title, body)
+}
+
+/// Deliver via the freedesktop notification service, falling back to the configured command.
+async fn send(config: &NotifyConfig, title: &str, body: &str) {
+    if config.dbus && send_dbus(title, body).await {
+        return;
+    }
+    if let Some(command) = &config.command
+        && !run_command(command, title, body).await
+    {
+        eprintln!("[notify] notification command failed: {command:?}");
+    }
+}
+
+/// Call `org.freedesktop.Notifications.Notify` through `gdbus`.
+async fn send_dbus(title: &str, body: &str) -> bool {
+    let mut cmd = Command::new("gdbus");
+    cmd.args([
+        "call",
+        "--session",
+        "--dest",
+        "org.freedesktop.Notifications",
+        "--object-path",
+        "/org/freedesktop/Notifications",
+        "--method",
+        "org.freedesktop.Notifications.Notify",
+        "bottycall",
+        "0",
+        "",
+    ])
+    .arg(gvariant_string(title))
+    .arg(gvariant_string(body))
+    .args(["[]", "{}", "-1"]);
+    run_quiet(cmd).await
+}
+
+/// Run the fallback command, substituting `{title}` and `{body}` in its arguments.
+async fn run_command(command: &[String], title: &str, body: &str) -> bool {
+    let Some((program, args)) = command.split_first() else {
+        return false;
+    };
+    let mut cmd = Command::new(program);
+    cmd.args(
+        args.iter()
+            .map(|a| a.replace("{title}", title).replace("{body}", body)),
+    );
+    run_quiet(cmd).await
+}
+
+async fn run_quiet(mut cmd: Command) -> bool {
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true);
+    matches!(
+        timeout(SEND_TIMEOUT, cmd.status()).await,
+        Ok(Ok(status)) if status.success()
+    )
+}
+
+/// Quote a string as a GVariant text literal so gdbus never reinterprets it.
+fn gvariant_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
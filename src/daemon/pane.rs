@@ -0,0 +1,222 @@
+/// Lines from the bottom searched for the status bar (tokens, model, cost, context).
+const STATUS_LINES: usize = 5;
+
+/// What a pane capture says about the session running in it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PaneSnapshot {
+    /// `NNNNN tokens` from the status bar.
+    pub tokens: Option<u64>,
+    /// Model name, e.g. "Opus 4.1" or "claude-sonnet-4-5".
+    pub model: Option<String>,
+    /// Context left until auto-compaction, in percent.
+    pub context_left: Option<u8>,
+    /// Session cost in USD, e.g. from `$1.23`.
+    pub cost_usd: Option<f64>,
+    /// The spinner line ending in "esc to interrupt)" is showing, so a turn is in progress.
+    pub working: bool,
+    /// A "Do you want to ...?" permission dialog is waiting for an answer.
+    pub permission_prompt: bool,
+    /// The dialog's second choice also approves, remembering the answer ("Yes, and don't
+    /// ask again ..." or "Yes, allow all edits ...").
+    pub always_allow: bool,
+}
+
+impl PaneSnapshot {
+    pub fn parse(content: &str) -> Self {
+        let mut lines: Vec<&str> = content.lines().map(str::trim_end).collect();
+        // capture-pane pads short output with blank rows
+        while lines.last().is_some_and(|l| l.is_empty()) {
+            lines.pop();
+        }
+        let status_bar = || lines.iter().rev().take(STATUS_LINES);
+        let choices = permission_choices(&lines);
+
+        Self {
+            tokens: status_bar().find_map(|l| parse_tokens_from_line(l)),
+            model: status_bar().find_map(|l| parse_model(l)),
+            context_left: lines.iter().rev().find_map(|l| parse_context_left(l)),
+            cost_usd: status_bar().find_map(|l| parse_cost(l)),
+            working: lines.iter().any(|l| l.contains("esc to interrupt)")),
+            permission_prompt: choices.first().is_some_and(|c| c.starts_with("Yes")),
+            always_allow: choices.get(1).is_some_and(|c| c.starts_with("Yes")),
+        }
+    }
+}
+
+/// Extract `NNNNN tokens` from the end of a line (Claude Code status bar format).
+fn parse_tokens_from_line(line: &str) -> Option<u64> {
+    let trimmed = line.trim_end().strip_suffix(" tokens")?;
+    let digits_start = trimmed
+        .rfind(|c: char| !c.is_ascii_digit())
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    let num = &trimmed[digits_start..];
+    if num.is_empty() {
+        return None;
+    }
+    num.parse().ok()
+}
+
+/// Find a model id (`claude-opus-4-1`) or family name with version (`Sonnet 4.5`).
+fn parse_model(line: &str) -> Option<String> {
+    let words: Vec<&str> = line
+        .split(|c: char| c.is_whitespace() || matches!(c, '[' | ']' | '(' | ')' | '|' | '│'))
+        .filter(|w| !w.is_empty())
+        .collect();
+
+    if let Some(id) = words.iter().find(|w| w.starts_with("claude-")) {
+        return Some(id.trim_end_matches([',', ':', '·']).to_string());
+    }
+
+    let i = words
+        .iter()
+        .position(|w| ["Opus", "Sonnet", "Haiku"].contains(w))?;
+    let version = words
+        .get(i + 1)
+        .filter(|v| v.starts_with(|c: char| c.is_ascii_digit()))
+        .map(|v| v.trim_end_matches(|c: char| !c.is_ascii_digit()));
+    Some(match version {
+        Some(v) => format!("{} {v}", words[i]),
+        None => words[i].to_string(),
+    })
+}
+
+/// "Context left until auto-compact: 12%" or "Context low (8% remaining)".
+fn parse_context_left(line: &str) -> Option<u8> {
+    let lower = line.to_ascii_lowercase();
+    let rest = if let Some(i) = lower.find("context left until auto-compact:") {
+        &lower[i + "context left until auto-compact:".len()..]
+    } else if let Some(i) = lower.find("context low (") {
+        &lower[i + "context low (".len()..]
+    } else {
+        return None;
+    };
+    let rest = rest.trim_start();
+    let end = rest.find('%')?;
+    rest[..end].trim().parse().ok()
+}
+
+/// The first `$N.NN` amount on the line.
+fn parse_cost(line: &str) -> Option<f64> {
+    let start = line.find('$')? + 1;
+    let amount: String = line[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    amount.trim_end_matches('.').parse().ok()
+}
+
+/// The numbered choices under the last "Do you want to ...?" question, without their
+/// numbers, e.g. `["Yes", "Yes, and don't ask again ...", "No, and tell Claude ..."]`.
+fn permission_choices<'a>(lines: &[&'a str]) -> Vec<&'a str> {
+    let Some(question) = lines
+        .iter()
+        .rposition(|l| l.to_ascii_lowercase().contains("do you want to"))
+    else {
+        return Vec::new();
+    };
+    lines[question + 1..]
+        .iter()
+        .filter_map(|l| {
+            let l = l.trim_matches(|c: char| c.is_whitespace() || matches!(c, '│' | '❯' | '>'));
+            let (number, choice) = l.split_once(". ")?;
+            number.parse::<u8>().ok()?;
+            Some(choice.trim_end())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! fixture {
+        ($name:literal) => {
+            include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/panes/", $name))
+        };
+    }
+
+    #[test]
+    fn working_pane() {
+        let snap = PaneSnapshot::parse(fixture!("working.txt"));
+        assert!(snap.working);
+        assert!(!snap.permission_prompt);
+        assert_eq!(snap.tokens, Some(48213));
+        assert_eq!(snap.model.as_deref(), Some("Opus 4.1"));
+        assert_eq!(snap.cost_usd, Some(1.87));
+        assert_eq!(snap.context_left, None);
+    }
+
+    #[test]
+    fn permission_prompt_pane() {
+        let snap = PaneSnapshot::parse(fixture!("permission.txt"));
+        assert!(snap.permission_prompt);
+        assert!(snap.always_allow);
+        assert!(!snap.working);
+    }
+
+    #[test]
+    fn edit_permission_prompt_pane() {
+        let snap = PaneSnapshot::parse(fixture!("permission_edit.txt"));
+        assert!(snap.permission_prompt);
+        assert!(snap.always_allow);
+    }
+
+    #[test]
+    fn prompt_without_always_choice() {
+        let pane = "Do you want to proceed?\n❯ 1. Yes\n  2. No (esc)\n";
+        let snap = PaneSnapshot::parse(pane);
+        assert!(snap.permission_prompt);
+        assert!(!snap.always_allow);
+    }
+
+    #[test]
+    fn idle_pane_with_low_context() {
+        let snap = PaneSnapshot::parse(fixture!("idle_context.txt"));
+        assert!(!snap.working);
+        assert!(!snap.permission_prompt);
+        assert_eq!(snap.context_left, Some(12));
+        assert_eq!(snap.model.as_deref(), Some("claude-sonnet-4-5-20250929"));
+        assert_eq!(snap.tokens, Some(151000));
+    }
+
+    #[test]
+    fn transcript_mentioning_prompts_is_not_a_prompt() {
+        let snap = PaneSnapshot::parse(fixture!("idle_transcript.txt"));
+        assert!(!snap.permission_prompt);
+        assert!(!snap.working);
+        assert_eq!(snap.tokens, None);
+        assert_eq!(snap.cost_usd, None);
+    }
+
+    #[test]
+    fn trailing_blank_rows_are_ignored() {
+        let padded = format!("{}{}", fixture!("working.txt"), "\n".repeat(12));
+        assert_eq!(
+            PaneSnapshot::parse(&padded),
+            PaneSnapshot::parse(fixture!("working.txt"))
+        );
+    }
+
+    #[test]
+    fn empty_pane() {
+        assert_eq!(PaneSnapshot::parse(""), PaneSnapshot::default());
+    }
+
+    #[test]
+    fn token_line() {
+        assert_eq!(parse_tokens_from_line("  ctx 12345 tokens"), Some(12345));
+        assert_eq!(parse_tokens_from_line("12345 tokens  "), Some(12345));
+        assert_eq!(parse_tokens_from_line("↑ 1.2k tokens)"), None);
+        assert_eq!(parse_tokens_from_line(" tokens"), None);
+    }
+
+    #[test]
+    fn context_line() {
+        assert_eq!(
+            parse_context_left("Context low (8% remaining) · Run /compact"),
+            Some(8)
+        );
+        assert_eq!(parse_context_left("the context window"), None);
+    }
+}
@@ -0,0 +1,120 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::Serialize;
+use tokio::sync::{Mutex, broadcast};
+use tokio::time::{Duration, sleep};
+
+use crate::protocol::ServerMessage;
+use crate::session::Session;
+
+use super::names::NameStore;
+use super::state::SessionMap;
+
+/// Location of the session snapshot file.
+/// Uses `$XDG_STATE_HOME/bottycall/sessions.json`, falling back to `~/.local/state`.
+pub fn state_path() -> PathBuf {
+    let base = std::env::var_os("XDG_STATE_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            let home = std::env::var_os("HOME").unwrap_or_default();
+            PathBuf::from(home).join(".local/state")
+        });
+    base.join("bottycall").join("sessions.json")
+}
+
+/// Location of the user-chosen names and labels, next to the session snapshot.
+pub fn names_path(state_path: &Path) -> PathBuf {
+    state_path.with_file_name("names.json")
+}
+
+/// Load a previously saved snapshot. A missing or unreadable file yields no sessions.
+pub fn load(path: &Path) -> Vec<Session> {
+    let Ok(data) = std::fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    match serde_json::from_str(&data) {
+        Ok(sessions) => sessions,
+        Err(e) => {
+            eprintln!("[persist] ignoring unreadable state file {}: {e}", path.display());
+            Vec::new()
+        }
+    }
+}
+
+/// Load saved names and labels. A missing or unreadable file yields none.
+pub fn load_names(path: &Path) -> NameStore {
+    let Ok(data) = std::fs::read_to_string(path) else {
+        return NameStore::default();
+    };
+    match serde_json::from_str(&data) {
+        Ok(names) => names,
+        Err(e) => {
+            eprintln!("[persist] ignoring unreadable names file {}: {e}", path.display());
+            NameStore::default()
+        }
+    }
+}
+
+/// Write the current sessions and names to disk atomically (temp file + rename). The
+/// files are written on the blocking pool, off the async workers.
+pub async fn save(state: &Arc<Mutex<SessionMap>>, path: &Path) {
+    let (sessions, names) = {
+        let map = state.lock().await;
+        (map.sessions(), map.names().clone())
+    };
+    let path = path.to_path_buf();
+    let written = tokio::task::spawn_blocking(move || {
+        if let Err(e) = write_json(&sessions, &path) {
+            eprintln!("[persist] failed to write {}: {e}", path.display());
+        }
+        let names_path = names_path(&path);
+        if let Err(e) = write_json(&names, &names_path) {
+            eprintln!("[persist] failed to write {}: {e}", names_path.display());
+        }
+    })
+    .await;
+    if let Err(e) = written {
+        eprintln!("[persist] save task failed: {e}");
+    }
+}
+
+fn write_json<T: Serialize + ?Sized>(value: &T, path: &Path) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_vec(value)?)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Shortest gap between two saves. Token counts alone update every session every few
+/// seconds; the daemon also saves on shutdown, so a crash loses at most this much.
+const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Save a snapshot after sessions change, at most once per `SAVE_INTERVAL`.
+pub async fn save_loop(
+    state: Arc<Mutex<SessionMap>>,
+    tx: broadcast::Sender<ServerMessage>,
+    path: PathBuf,
+) {
+    let mut rx = tx.subscribe();
+
+    loop {
+        match rx.recv().await {
+            Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+
+        // Fold every change until the interval is up into one write
+        sleep(SAVE_INTERVAL).await;
+        while !matches!(
+            rx.try_recv(),
+            Err(broadcast::error::TryRecvError::Empty | broadcast::error::TryRecvError::Closed)
+        ) {}
+
+        save(&state, &path).await;
+    }
+}
@@ -1,143 +1,266 @@
 use std::collections::{HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::sync::Arc;
 
-use tokio::process::Command;
+use chrono::Utc;
 use tokio::sync::{Mutex, broadcast};
 use tokio::time::{Duration, interval};
 
+use crate::mux::MuxKind;
+use crate::procs::{ProcessInfo, ProcessSource};
 use crate::protocol::ServerMessage;
 
+use super::pane::PaneSnapshot;
 use super::state::SessionMap;
 
 /// Run a single poll: discover claude processes, register new sessions, remove stale ones.
-pub async fn poll_once(state: &Arc<Mutex<SessionMap>>, tx: &broadcast::Sender<ServerMessage>) {
-    let Some(panes) = list_tmux_panes().await else {
+pub async fn poll_once(
+    state: &Arc<Mutex<SessionMap>>,
+    tx: &broadcast::Sender<ServerMessage>,
+    processes: &Arc<dyn ProcessSource>,
+    process_name: &str,
+) {
+    let Some(discovery) = discover_claude(processes, process_name).await else {
         return;
     };
 
-    let pane_by_pid: HashMap<u32, (&str, &str)> = panes
+    let mut map = state.lock().await;
+    sync_sessions(&mut map, &discovery, tx);
+}
+
+/// Reconcile sessions restored from disk against the live panes and claude processes,
+/// then poll as usual. Restored sessions whose pane still runs the same claude process, or
+/// whose process is still running, keep their session_id, status and tokens.
+pub async fn reconcile_restored(
+    state: &Arc<Mutex<SessionMap>>,
+    tx: &broadcast::Sender<ServerMessage>,
+    processes: &Arc<dyn ProcessSource>,
+    process_name: &str,
+) {
+    // An unreadable process table means none of the restored sessions can be confirmed
+    let discovery = discover_claude(processes, process_name)
+        .await
+        .unwrap_or_default();
+
+    let live_panes: HashMap<String, u32> = discovery
+        .claude
         .iter()
-        .map(|p| (p.pane_pid, (p.pane_id.as_str(), p.cwd.as_str())))
+        .filter_map(|c| Some((c.pane.clone()?.1, c.pid)))
         .collect();
-
-    let claude_panes = find_claude_panes(&pane_by_pid).await;
+    let live_pids: HashSet<u32> = discovery.claude.iter().map(|c| c.pid).collect();
 
     let mut map = state.lock().await;
+    for msg in map.retain_live(&live_panes, &live_pids) {
+        let _ = tx.send(msg);
+    }
+    sync_sessions(&mut map, &discovery, tx);
+}
+
+/// Claude processes found by one scan.
+#[derive(Default)]
+struct Discovery {
+    claude: Vec<ClaudeProcess>,
+    /// Multiplexers whose panes were listed. A backend with no server running lists zero
+    /// panes, so its polled sessions are dropped; one that failed to run isn't listed and
+    /// says nothing about its panes.
+    listed: HashSet<MuxKind>,
+}
+
+/// Scan the process table for claude and match each process to its multiplexer pane.
+/// `None` if the process table can't be read.
+async fn discover_claude(
+    processes: &Arc<dyn ProcessSource>,
+    process_name: &str,
+) -> Option<Discovery> {
+    // Read the process table once and share it with every multiplexer backend
+    let source = Arc::clone(processes);
+    let name = process_name.to_string();
+    let scan = tokio::task::spawn_blocking(move || {
+        let (entries, cwds) = scan_processes(&*source, &name)?;
+        let listing = MuxKind::ALL
+            .into_iter()
+            .filter_map(|kind| Some((kind, kind.backend().list_panes(&*source, &entries)?)))
+            .collect::<Vec<_>>();
+        Some((entries, cwds, listing))
+    })
+    .await;
+    let Ok(Some((entries, claude_cwds, listing))) = scan else {
+        eprintln!("[poller] failed to list processes");
+        return None;
+    };
+
+    let listed = listing.iter().map(|(kind, _)| *kind).collect();
+    let pane_by_pid: HashMap<u32, PaneRef> = listing
+        .iter()
+        .flat_map(|(kind, panes)| {
+            panes.iter().map(|p| {
+                (p.pid, PaneRef { mux: *kind, pane_id: &p.pane_id, cwd: &p.cwd })
+            })
+        })
+        .collect();
+
+    let claude = find_claude_processes(&pane_by_pid, &entries, &claude_cwds, process_name);
+    Some(Discovery { claude, listed })
+}
+
+/// Register polled sessions for new claude panes and processes, and drop polled sessions
+/// whose pane or process is gone.
+fn sync_sessions(
+    map: &mut SessionMap,
+    discovery: &Discovery,
+    tx: &broadcast::Sender<ServerMessage>,
+) {
     let known_panes = map.pane_session_map();
+    let known_pids = map.pid_session_map();
 
-    let active_pane_ids: HashSet<&str> =
-        claude_panes.iter().map(|c| c.pane_id.as_str()).collect();
+    let active_pane_ids: HashSet<&str> = discovery
+        .claude
+        .iter()
+        .filter_map(|c| Some(c.pane.as_ref()?.1.as_str()))
+        .collect();
+    let active_pids: HashSet<u32> = discovery.claude.iter().map(|c| c.pid).collect();
 
-    for cp in &claude_panes {
-        if known_panes.contains_key(&cp.pane_id) {
-            continue;
-        }
-        let session_id = format!("polled-{}", cp.pane_id.trim_start_matches('%'));
-        if let Some(msg) = map.register_polled(session_id, cp.cwd.clone(), cp.pane_id.clone()) {
+    for cp in &discovery.claude {
+        let session_id = match &cp.pane {
+            Some((_, pane_id)) if !known_panes.contains_key(pane_id) => {
+                format!("polled-{}", pane_id.trim_start_matches('%'))
+            }
+            None if !known_pids.contains_key(&cp.pid) => format!("polled-pid-{}", cp.pid),
+            _ => continue,
+        };
+        let msgs = map.register_polled(
+            session_id,
+            cp.cwd.clone(),
+            cp.pane.clone(),
+            cp.pid,
+            cp.tty.clone(),
+        );
+        for msg in msgs {
             let _ = tx.send(msg);
         }
     }
 
-    let to_remove: Vec<String> = known_panes
+    let gone_panes = known_panes
         .iter()
         .filter(|(pane_id, session_id)| {
-            session_id.starts_with("polled-") && !active_pane_ids.contains(pane_id.as_str())
+            let mux = map.get(session_id).and_then(|s| s.mux).unwrap_or_default();
+            discovery.listed.contains(&mux) && !active_pane_ids.contains(pane_id.as_str())
         })
-        .map(|(_, session_id)| session_id.clone())
+        .map(|(_, session_id)| session_id);
+    let gone_pids = known_pids
+        .iter()
+        .filter(|(pid, _)| !active_pids.contains(pid))
+        .map(|(_, session_id)| session_id);
+    let to_remove: Vec<String> = gone_panes
+        .chain(gone_pids)
+        .filter(|session_id| session_id.starts_with("polled-"))
+        .cloned()
         .collect();
 
     for session_id in to_remove {
-        if let Some(msg) = map.remove(&session_id) {
+        for msg in map.remove(&session_id) {
             let _ = tx.send(msg);
         }
     }
 }
 
-/// Periodically scan for Claude Code processes and match them to tmux panes.
-pub async fn poll_loop(state: Arc<Mutex<SessionMap>>, tx: broadcast::Sender<ServerMessage>) {
-    let mut ticker = interval(Duration::from_secs(5));
+/// Periodically scan for Claude Code processes and match them to multiplexer panes.
+pub async fn poll_loop(
+    state: Arc<Mutex<SessionMap>>,
+    tx: broadcast::Sender<ServerMessage>,
+    every: Duration,
+    processes: Arc<dyn ProcessSource>,
+    process_name: String,
+) {
+    let mut ticker = interval(every);
 
     loop {
         ticker.tick().await;
-        poll_once(&state, &tx).await;
+        poll_once(&state, &tx, &processes, &process_name).await;
     }
 }
 
-/// Periodically capture tmux pane content to get live token counts from Claude Code's status bar.
-pub async fn token_poll_loop(state: Arc<Mutex<SessionMap>>, tx: broadcast::Sender<ServerMessage>) {
-    let mut ticker = interval(Duration::from_secs(2));
+/// Periodically capture pane content to scrape Claude Code's status bar, spinner and
+/// permission dialogs, and to notice whether the pane is still changing.
+pub async fn token_poll_loop(
+    state: Arc<Mutex<SessionMap>>,
+    tx: broadcast::Sender<ServerMessage>,
+    every: Duration,
+) {
+    let mut ticker = interval(every);
 
     loop {
         ticker.tick().await;
 
         // Snapshot sessions with panes without holding the lock during I/O
-        let pane_sessions: Vec<(String, String)> = {
+        let pane_sessions: Vec<(String, MuxKind, String)> = {
             let map = state.lock().await;
             map.sessions()
                 .into_iter()
-                .filter_map(|s| s.tmux_pane.map(|p| (s.session_id, p)))
+                .filter_map(|s| {
+                    let (mux, pane_id) = s.pane()?;
+                    Some((s.session_id.clone(), mux, pane_id.to_string()))
+                })
                 .collect()
         };
 
-        for (session_id, pane_id) in pane_sessions {
-            if let Some(tokens) = read_pane_tokens(&pane_id).await {
-                let mut map = state.lock().await;
-                if let Some(msg) = map.update_pane_tokens(&session_id, tokens) {
-                    let _ = tx.send(msg);
-                }
+        for (session_id, mux, pane_id) in pane_sessions {
+            let captured_at = Utc::now();
+            let capture =
+                tokio::task::spawn_blocking(move || mux.backend().capture_pane(&pane_id)).await;
+            let Ok(Some(content)) = capture else {
+                continue;
+            };
+            let snapshot = PaneSnapshot::parse(&content);
+            let mut map = state.lock().await;
+            if let Some(msg) = map.observe_pane(&session_id, pane_fingerprint(&content)) {
+                let _ = tx.send(msg);
+            }
+            if let Some(msg) = map.apply_pane(&session_id, &snapshot, captured_at) {
+                let _ = tx.send(msg);
             }
         }
     }
 }
 
-/// Capture a tmux pane and extract the token count from Claude Code's status bar.
-/// The status bar displays `NNNNN tokens` right-aligned near the bottom of the pane.
-async fn read_pane_tokens(pane_id: &str) -> Option<u64> {
-    let output = Command::new("tmux")
-        .args(["capture-pane", "-t", pane_id, "-p"])
-        .output()
-        .await
-        .ok()?;
-
-    if !output.status.success() {
-        return None;
+/// Hash of a pane's content for progress detection. The spinner line and all digits are
+/// left out so ticking timers and token counters don't look like progress.
+fn pane_fingerprint(content: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for line in content.lines().filter(|l| !l.contains("esc to interrupt")) {
+        for c in line.chars().filter(|c| !c.is_ascii_digit()) {
+            c.hash(&mut hasher);
+        }
+        '\n'.hash(&mut hasher);
     }
-
-    let content = String::from_utf8_lossy(&output.stdout);
-
-    // Search last 5 lines — status bar is always near the bottom
-    content
-        .lines()
-        .rev()
-        .take(5)
-        .find_map(parse_tokens_from_line)
-}
-
-/// Extract `NNNNN tokens` from the end of a line (Claude Code status bar format).
-fn parse_tokens_from_line(line: &str) -> Option<u64> {
-    let trimmed = line.trim_end().strip_suffix(" tokens")?;
-    let digits_start = trimmed
-        .rfind(|c: char| !c.is_ascii_digit())
-        .map(|i| i + 1)
-        .unwrap_or(0);
-    let num = &trimmed[digits_start..];
-    if num.is_empty() { return None; }
-    num.parse().ok()
+    hasher.finish()
 }
 
-struct ClaudePane {
-    pane_id: String,
+/// A running claude process, in a multiplexer pane or directly on a terminal.
+struct ClaudeProcess {
+    pid: u32,
+    tty: Option<String>,
     cwd: String,
+    pane: Option<(MuxKind, String)>,
 }
 
-/// Find all `claude` processes and trace each to its tmux pane.
-async fn find_claude_panes(pane_by_pid: &HashMap<u32, (&str, &str)>) -> Vec<ClaudePane> {
-    // Get all process parent relationships in one shot
-    let Some(ps_entries) = list_all_processes().await else {
-        eprintln!("[poller] failed to list processes");
-        return Vec::new();
-    };
+/// A listed pane, keyed by the pid of the process it was started with.
+struct PaneRef<'a> {
+    mux: MuxKind,
+    pane_id: &'a str,
+    cwd: &'a str,
+}
 
+/// Find all `claude` processes and trace each to its multiplexer pane. The session's cwd is the
+/// claude process's own working directory when readable, else the pane's current path.
+/// Processes outside any multiplexer are kept when they run on a terminal; ones without a tty are
+/// scripted runs (`claude -p` in a pipeline, editor integrations) rather than sessions.
+fn find_claude_processes(
+    pane_by_pid: &HashMap<u32, PaneRef<'_>>,
+    ps_entries: &[ProcessInfo],
+    claude_cwds: &HashMap<u32, String>,
+    process_name: &str,
+) -> Vec<ClaudeProcess> {
     eprintln!("[poller] parsed {} processes, {} pane pids", ps_entries.len(), pane_by_pid.len());
 
     let parent_map: HashMap<u32, u32> = ps_entries
@@ -145,29 +268,43 @@ async fn find_claude_panes(pane_by_pid: &HashMap<u32, (&str, &str)>) -> Vec<Clau
         .map(|e| (e.pid, e.ppid))
         .collect();
 
-    // Find PIDs of actual `claude` binaries (match by process name, not command line)
-    let claude_pids: Vec<u32> = ps_entries
+    // Find actual `claude` binaries (match by process name, not command line)
+    let claude_procs: Vec<&ProcessInfo> = ps_entries
         .iter()
-        .filter(|e| e.comm == "claude")
-        .map(|e| e.pid)
+        .filter(|e| e.comm == process_name)
         .collect();
 
-    eprintln!("[poller] found {} claude processes: {:?}", claude_pids.len(), claude_pids);
+    eprintln!(
+        "[poller] found {} claude processes: {:?}",
+        claude_procs.len(),
+        claude_procs.iter().map(|e| e.pid).collect::<Vec<_>>()
+    );
 
     let mut result = Vec::new();
     let mut seen_panes: HashSet<String> = HashSet::new();
 
-    for cpid in claude_pids {
-        // Walk up the parent chain to find the tmux pane
+    for claude in claude_procs {
+        let cpid = claude.pid;
+        // Walk up the parent chain to find the pane
         let mut current = cpid;
         let mut hops = 0;
         loop {
-            if let Some((pane_id, cwd)) = pane_by_pid.get(&current) {
-                eprintln!("[poller] claude {} -> pane {} (cwd: {}) in {} hops", cpid, pane_id, cwd, hops);
-                if seen_panes.insert(pane_id.to_string()) {
-                    result.push(ClaudePane {
-                        pane_id: pane_id.to_string(),
+            if let Some(pane) = pane_by_pid.get(&current) {
+                let cwd = claude_cwds.get(&cpid).map_or(pane.cwd, String::as_str);
+                eprintln!(
+                    "[poller] claude {} -> {} pane {} (cwd: {}) in {} hops",
+                    cpid,
+                    pane.mux.label(),
+                    pane.pane_id,
+                    cwd,
+                    hops
+                );
+                if seen_panes.insert(pane.pane_id.to_string()) {
+                    result.push(ClaudeProcess {
+                        pid: cpid,
+                        tty: claude.tty.clone(),
                         cwd: cwd.to_string(),
+                        pane: Some((pane.mux, pane.pane_id.to_string())),
                     });
                 }
                 break;
@@ -178,7 +315,17 @@ async fn find_claude_panes(pane_by_pid: &HashMap<u32, (&str, &str)>) -> Vec<Clau
                     hops += 1;
                 }
                 _ => {
-                    eprintln!("[poller] claude {} -> no pane found (stopped at pid {} after {} hops)", cpid, current, hops);
+                    if let (Some(tty), Some(cwd)) = (&claude.tty, claude_cwds.get(&cpid)) {
+                        eprintln!("[poller] claude {} -> no pane, tty {} (cwd: {})", cpid, tty, cwd);
+                        result.push(ClaudeProcess {
+                            pid: cpid,
+                            tty: Some(tty.clone()),
+                            cwd: cwd.clone(),
+                            pane: None,
+                        });
+                    } else {
+                        eprintln!("[poller] claude {} -> no pane or tty found (stopped at pid {} after {} hops)", cpid, current, hops);
+                    }
                     break;
                 }
             }
@@ -188,79 +335,50 @@ async fn find_claude_panes(pane_by_pid: &HashMap<u32, (&str, &str)>) -> Vec<Clau
     result
 }
 
-struct PsEntry {
-    pid: u32,
-    ppid: u32,
-    comm: String,
-}
-
-/// List all processes with pid, ppid, and command name.
-async fn list_all_processes() -> Option<Vec<PsEntry>> {
-    let output = Command::new("ps")
-        .args(["-eo", "pid,ppid,comm"])
-        .output()
-        .await
-        .ok()?;
-
-    if !output.status.success() {
-        return None;
-    }
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let entries = stdout
-        .lines()
-        .skip(1) // header
-        .filter_map(|line| {
-            let mut parts = line.split_whitespace();
-            let pid: u32 = parts.next()?.parse().ok()?;
-            let ppid: u32 = parts.next()?.parse().ok()?;
-            let comm = parts.next()?.to_string();
-            // Extract just the binary name from the path
-            let comm = comm
-                .rsplit('/')
-                .next()
-                .unwrap_or(&comm)
-                .trim_start_matches('-') // login shells show as -zsh
-                .to_string();
-            Some(PsEntry { pid, ppid, comm })
-        })
+/// Read the process table and the working directory of every process named `process_name`.
+fn scan_processes(
+    source: &dyn ProcessSource,
+    process_name: &str,
+) -> Option<(Vec<ProcessInfo>, HashMap<u32, String>)> {
+    let entries = source.processes()?;
+    let cwds = entries
+        .iter()
+        .filter(|e| e.comm == process_name)
+        .filter_map(|e| Some((e.pid, source.cwd(e.pid)?)))
         .collect();
-
-    Some(entries)
+    Some((entries, cwds))
 }
 
-struct TmuxPane {
-    pane_id: String,
-    pane_pid: u32,
-    cwd: String,
-}
-
-async fn list_tmux_panes() -> Option<Vec<TmuxPane>> {
-    let output = Command::new("tmux")
-        .args(["list-panes", "-a", "-F", "#{pane_id} #{pane_pid} #{pane_current_path}"])
-        .output()
-        .await
-        .ok()?;
-
-    if !output.status.success() {
-        return None;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::names::NameStore;
+    use crate::session::SlugStyle;
+
+    fn map_with_polled_pane() -> SessionMap {
+        let mut map = SessionMap::new(10, NameStore::default(), SlugStyle::default());
+        let pane = Some((MuxKind::Tmux, "%3".to_string()));
+        map.register_polled("polled-3".to_string(), "/tmp".to_string(), pane, 100, None);
+        map
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let panes = stdout
-        .lines()
-        .filter_map(|line| {
-            let mut parts = line.splitn(3, ' ');
-            let pane_id = parts.next()?.to_string();
-            let pane_pid: u32 = parts.next()?.parse().ok()?;
-            let cwd = parts.next()?.to_string();
-            Some(TmuxPane {
-                pane_id,
-                pane_pid,
-                cwd,
-            })
-        })
-        .collect();
+    #[test]
+    fn tmux_listed_without_panes_drops_polled_sessions() {
+        let (tx, _rx) = broadcast::channel(16);
+        let mut map = map_with_polled_pane();
+        let discovery = Discovery {
+            claude: Vec::new(),
+            listed: HashSet::from([MuxKind::Tmux]),
+        };
+        sync_sessions(&mut map, &discovery, &tx);
+        assert!(map.get("polled-3").is_none());
+    }
 
-    Some(panes)
+    #[test]
+    fn unlisted_tmux_keeps_polled_sessions() {
+        let (tx, _rx) = broadcast::channel(16);
+        let mut map = map_with_polled_pane();
+        sync_sessions(&mut map, &Discovery::default(), &tx);
+        assert!(map.get("polled-3").is_some());
+    }
 }
@@ -0,0 +1,162 @@
+use std::process::Stdio;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::{Mutex, broadcast};
+use tokio::time::{Duration, sleep, timeout};
+
+use crate::config::SinkConfig;
+use crate::protocol::ServerMessage;
+use crate::session::{Session, SessionFlag, Status};
+
+use super::state::SessionMap;
+use super::transitions::{Transition, watch_transitions};
+
+/// JSON delivered to webhooks (as the POST body) and commands (on stdin).
+#[derive(Serialize)]
+struct Payload<'a> {
+    from: Option<Status>,
+    to: Status,
+    /// Set when the transition is a session being flagged as stalled or forgotten.
+    flagged: Option<SessionFlag>,
+    ts: DateTime<Utc>,
+    session: &'a Session,
+}
+
+/// Forward status transitions to the configured webhooks and commands.
+pub async fn sink_loop(
+    state: Arc<Mutex<SessionMap>>,
+    tx: broadcast::Sender<ServerMessage>,
+    sinks: Vec<SinkConfig>,
+) {
+    let sinks = Arc::new(sinks);
+    watch_transitions(&state, &tx, |transition| {
+        let payload = Payload {
+            from: transition.from,
+            to: transition.to,
+            flagged: transition.flagged,
+            ts: Utc::now(),
+            session: &transition.session,
+        };
+        let Ok(body) = serde_json::to_vec(&payload) else {
+            return;
+        };
+        let body = Arc::new(body);
+
+        for (i, sink) in sinks.iter().enumerate() {
+            if !matches(sink, &transition) {
+                continue;
+            }
+            let sinks = Arc::clone(&sinks);
+            let body = Arc::clone(&body);
+            // Each delivery runs on its own task so retries never hold up the broadcast
+            tokio::spawn(async move {
+                deliver(i, &sinks[i], &body).await;
+            });
+        }
+    })
+    .await;
+}
+
+/// An empty status filter list matches every status; flags only fire when listed.
+fn matches(sink: &SinkConfig, t: &Transition) -> bool {
+    if let Some(flag) = t.flagged
+        && sink.flags.contains(&flag)
+    {
+        return true;
+    }
+    if !t.status_changed() {
+        return false;
+    }
+    let from_ok = sink.from.is_empty() || t.from.is_some_and(|f| sink.from.contains(&f));
+    let to_ok = sink.to.is_empty() || sink.to.contains(&t.to);
+    from_ok && to_ok
+}
+
+/// Try a delivery, retrying with a growing delay on failure. `index` is the sink's
+/// position in the config, used to say which one failed.
+async fn deliver(index: usize, sink: &SinkConfig, body: &[u8]) {
+    let limit = Duration::from_secs(sink.timeout_secs.max(1));
+
+    for attempt in 0..=sink.retries {
+        if attempt > 0 {
+            sleep(Duration::from_secs(1 << (attempt - 1).min(5))).await;
+        }
+        let result = match (&sink.url, &sink.command) {
+            (Some(url), _) => post(url, body, limit).await,
+            (None, Some(command)) => run_command(command, body, limit).await,
+            (None, None) => return,
+        };
+        match result {
+            Ok(()) => return,
+            Err(e) => eprintln!(
+                "[sinks] sink {index} ({}): delivery attempt {} failed: {e}",
+                target(sink),
+                attempt + 1
+            ),
+        }
+    }
+}
+
+/// Where a sink delivers to, for log messages.
+fn target(sink: &SinkConfig) -> String {
+    match (&sink.url, &sink.command) {
+        (Some(url), _) => url.clone(),
+        (None, Some(command)) => command.join(" "),
+        (None, None) => "nowhere".to_string(),
+    }
+}
+
+/// POST the payload with curl, which is already present wherever tmux and git are.
+async fn post(url: &str, body: &[u8], limit: Duration) -> anyhow::Result<()> {
+    let mut cmd = Command::new("curl");
+    cmd.args([
+        "--silent",
+        "--show-error",
+        "--fail",
+        "--max-time",
+        &limit.as_secs().to_string(),
+        "-X",
+        "POST",
+        "-H",
+        "Content-Type: application/json",
+        "--data-binary",
+        "@-",
+        url,
+    ]);
+    pipe(cmd, body, limit).await
+}
+
+async fn run_command(command: &[String], body: &[u8], limit: Duration) -> anyhow::Result<()> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| anyhow::anyhow!("empty sink command"))?;
+    let mut cmd = Command::new(program);
+    cmd.args(args);
+    pipe(cmd, body, limit).await
+}
+
+/// Run a process with `body` on stdin, killing it if it outlives `limit`.
+async fn pipe(mut cmd: Command, body: &[u8], limit: Duration) -> anyhow::Result<()> {
+    cmd.stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true);
+
+    let run = async {
+        let mut child = cmd.spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(body).await?;
+        }
+        let status = child.wait().await?;
+        anyhow::ensure!(status.success(), "exited with {status}");
+        Ok(())
+    };
+
+    timeout(limit, run)
+        .await
+        .map_err(|_| anyhow::anyhow!("timed out after {}s", limit.as_secs()))?
+}
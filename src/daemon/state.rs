@@ -1,19 +1,38 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 
-use chrono::Utc;
+use chrono::{DateTime, Duration, Utc};
 
+use crate::mux::MuxKind;
 use crate::protocol::{HookReport, ServerMessage};
-use crate::session::{Session, Status, git_branch_from_cwd, git_repo_from_cwd, slug_from_cwd};
+use crate::session::{
+    HistoryEvent, Session, SessionFlag, SlugStyle, Status, assign_slugs, git_branch_from_cwd,
+    git_repo_from_cwd, git_worktree_from_cwd,
+};
+
+use super::names::{NameKey, NameStore, Naming};
+use super::pane::PaneSnapshot;
 
 /// Holds all tracked sessions keyed by session_id.
 pub struct SessionMap {
     sessions: HashMap<String, Session>,
+    /// Bounded ring of recent hook events per session.
+    history: HashMap<String, VecDeque<HistoryEvent>>,
+    history_len: usize,
+    names: NameStore,
+    slug_style: SlugStyle,
+    /// Fingerprint of each session's pane content and when it last changed.
+    pane_activity: HashMap<String, (u64, DateTime<Utc>)>,
 }
 
 impl SessionMap {
-    pub fn new() -> Self {
+    pub fn new(history_len: usize, names: NameStore, slug_style: SlugStyle) -> Self {
         Self {
             sessions: HashMap::new(),
+            history: HashMap::new(),
+            history_len,
+            names,
+            slug_style,
+            pane_activity: HashMap::new(),
         }
     }
 
@@ -21,6 +40,197 @@ impl SessionMap {
         self.sessions.values().cloned().collect()
     }
 
+    pub fn get(&self, session_id: &str) -> Option<&Session> {
+        self.sessions.get(session_id)
+    }
+
+    /// Find a session by exact id, unique id prefix, or unique slug.
+    pub fn resolve(&self, query: &str) -> Result<String, String> {
+        if self.sessions.contains_key(query) {
+            return Ok(query.to_string());
+        }
+
+        let by_prefix: Vec<&Session> = self
+            .sessions
+            .values()
+            .filter(|s| s.session_id.starts_with(query))
+            .collect();
+        let candidates = if by_prefix.is_empty() {
+            self.sessions
+                .values()
+                .filter(|s| s.slug == query)
+                .collect()
+        } else {
+            by_prefix
+        };
+
+        match candidates.as_slice() {
+            [one] => Ok(one.session_id.clone()),
+            [] => Err(format!("no session matches {query:?}")),
+            many => Err(format!(
+                "{query:?} is ambiguous: {}",
+                many.iter()
+                    .map(|s| s.session_id.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+
+    /// Manually override a session's status.
+    pub fn set_status(&mut self, session_id: &str, status: Status) -> Option<ServerMessage> {
+        let session = self.sessions.get_mut(session_id)?;
+        if session.status != status {
+            session.status = status;
+            session.status_since = Some(Utc::now());
+        }
+        session.flag = None;
+        Some(ServerMessage::Update {
+            session: Box::new(session.clone()),
+        })
+    }
+
+    /// User-chosen names and labels, for persisting.
+    pub fn names(&self) -> &NameStore {
+        &self.names
+    }
+
+    /// Set or clear a session's display name, or its directory's with `dir`.
+    pub fn rename(
+        &mut self,
+        session_id: &str,
+        name: Option<String>,
+        dir: bool,
+    ) -> Result<Vec<ServerMessage>, String> {
+        self.update_naming(session_id, dir, |n| n.name = name)
+    }
+
+    /// Replace a session's labels, or its directory's with `dir`. An empty list on a
+    /// session overrides its directory's labels rather than falling back to them.
+    pub fn set_labels(
+        &mut self,
+        session_id: &str,
+        labels: Vec<String>,
+        dir: bool,
+    ) -> Result<Vec<ServerMessage>, String> {
+        let labels = (!dir || !labels.is_empty()).then_some(labels);
+        self.update_naming(session_id, dir, |n| n.labels = labels)
+    }
+
+    /// Store a naming change and re-apply it to every session it covers.
+    fn update_naming(
+        &mut self,
+        session_id: &str,
+        dir: bool,
+        f: impl FnOnce(&mut Naming),
+    ) -> Result<Vec<ServerMessage>, String> {
+        let cwd = self
+            .sessions
+            .get(session_id)
+            .ok_or("session vanished")?
+            .cwd
+            .clone();
+        let key = if dir {
+            NameKey::Dir(cwd.clone().ok_or("session has no working directory")?)
+        } else {
+            NameKey::Session(session_id.to_string())
+        };
+        self.names.update(key, f);
+
+        let names = &self.names;
+        let mut msgs: Vec<ServerMessage> = self
+            .sessions
+            .values_mut()
+            .filter(|s| s.session_id == session_id || (dir && s.cwd == cwd))
+            .map(|s| {
+                apply_naming(names, s);
+                ServerMessage::Update {
+                    session: Box::new(s.clone()),
+                }
+            })
+            .collect();
+        self.refresh_slugs(&mut msgs);
+        Ok(msgs)
+    }
+
+    /// Re-derive every slug after sessions came, went or moved, queueing an `Update`
+    /// for each session whose slug changed (or refreshing one already in `msgs`).
+    fn refresh_slugs(&mut self, msgs: &mut Vec<ServerMessage>) {
+        for id in assign_slugs(self.sessions.values_mut(), self.slug_style) {
+            let session = Box::new(self.sessions[&id].clone());
+            let queued = msgs.iter_mut().find_map(|m| match m {
+                ServerMessage::Update { session } if session.session_id == id => Some(session),
+                _ => None,
+            });
+            match queued {
+                Some(queued) => *queued = session,
+                None => msgs.push(ServerMessage::Update { session }),
+            }
+        }
+    }
+
+    /// Recent hook events for a session, oldest first.
+    pub fn history(&self, session_id: &str) -> Vec<HistoryEvent> {
+        self.history
+            .get(session_id)
+            .map(|ring| ring.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn record_event(&mut self, report: &HookReport, ts: chrono::DateTime<Utc>) {
+        if self.history_len == 0 {
+            return;
+        }
+        let ring = self.history.entry(report.session_id.clone()).or_default();
+        if ring.len() == self.history_len {
+            ring.pop_front();
+        }
+        ring.push_back(HistoryEvent {
+            event: report.hook_event_name.clone(),
+            tool_name: report.tool_name.clone(),
+            message: report.message.clone(),
+            ts,
+        });
+    }
+
+    /// Seed the map with sessions loaded from a previous daemon run.
+    pub fn restore(&mut self, sessions: Vec<Session>) {
+        for mut session in sessions {
+            apply_naming(&self.names, &mut session);
+            self.sessions.insert(session.session_id.clone(), session);
+        }
+        let sessions = &self.sessions;
+        self.names.retain_sessions(|id| sessions.contains_key(id));
+        assign_slugs(self.sessions.values_mut(), self.slug_style);
+    }
+
+    /// Drop sessions whose pane no longer runs their claude process, or for sessions
+    /// outside a multiplexer whose claude process is gone. `live_panes` maps each pane
+    /// running claude to that process's pid. Multiplexers reuse pane ids after a restart,
+    /// so a pane session is only kept if its recorded pid, when known, matches too.
+    /// Sessions with neither pane nor pid can't be verified and are kept.
+    pub fn retain_live(
+        &mut self,
+        live_panes: &HashMap<String, u32>,
+        live_pids: &HashSet<u32>,
+    ) -> Vec<ServerMessage> {
+        let stale: Vec<String> = self
+            .sessions
+            .values()
+            .filter(|s| match (&s.tmux_pane, s.pid) {
+                (Some(pane), pid) => match live_panes.get(pane) {
+                    Some(live) => pid.is_some_and(|pid| pid != *live),
+                    None => true,
+                },
+                (None, Some(pid)) => !live_pids.contains(&pid),
+                (None, None) => false,
+            })
+            .map(|s| s.session_id.clone())
+            .collect();
+
+        stale.iter().flat_map(|id| self.remove(id)).collect()
+    }
+
     /// Apply a hook report and return server messages to broadcast.
     pub fn apply_report(&mut self, report: &HookReport) -> Vec<ServerMessage> {
         let event = report.hook_event_name.as_str();
@@ -28,73 +238,117 @@ impl SessionMap {
 
         // SessionEnd -> remove
         if event == "SessionEnd" {
-            if self.sessions.remove(&report.session_id).is_some() {
-                return vec![ServerMessage::Remove {
-                    session_id: report.session_id.clone(),
-                }];
-            }
-            return vec![];
+            return self.remove(&report.session_id);
         }
 
-        // If this hook report has a tmux_pane, evict any polled session occupying it
-        let polled_remove = if let Some(pane) = &report.tmux_pane {
-            let polled_id = self
-                .sessions
-                .values()
-                .find(|s| {
-                    s.session_id.starts_with("polled-")
-                        && s.tmux_pane.as_ref() == Some(pane)
-                })
-                .map(|s| s.session_id.clone());
-            if let Some(id) = polled_id {
-                self.sessions.remove(&id);
-                Some(ServerMessage::Remove { session_id: id })
-            } else {
-                None
-            }
-        } else {
-            None
-        };
+        self.record_event(report, now);
+
+        // Evict any polled session standing in for this one, by pane or by process
+        let polled_id = self
+            .sessions
+            .values()
+            .find(|s| {
+                s.session_id.starts_with("polled-")
+                    && ((report.tmux_pane.is_some() && s.tmux_pane == report.tmux_pane)
+                        || (report.claude_pid.is_some() && s.pid == report.claude_pid))
+            })
+            .map(|s| s.session_id.clone());
+        let polled_remove = polled_id.map(|id| self.remove(&id)).unwrap_or_default();
 
+        let names = &self.names;
         let session = self
             .sessions
             .entry(report.session_id.clone())
             .or_insert_with(|| {
                 let cwd = report.cwd.clone().unwrap_or_default();
-                let (slug, git_repo, git_branch) = if cwd.is_empty() {
-                    (report.session_id.chars().take(8).collect(), None, None)
+                let (git_repo, git_branch, git_worktree) = if cwd.is_empty() {
+                    (None, None, None)
                 } else {
-                    (slug_from_cwd(&cwd), git_repo_from_cwd(&cwd), git_branch_from_cwd(&cwd))
+                    (
+                        git_repo_from_cwd(&cwd),
+                        git_branch_from_cwd(&cwd),
+                        git_worktree_from_cwd(&cwd),
+                    )
                 };
-                Session {
+                let mut session = Session {
                     session_id: report.session_id.clone(),
-                    slug,
                     status: Status::Idle,
                     last_activity: now,
                     cwd: report.cwd.clone(),
                     tmux_pane: report.tmux_pane.clone(),
+                    mux: report.tmux_pane.as_ref().map(|_| report.mux.unwrap_or_default()),
+                    pid: report.claude_pid,
+                    tty: report.tty.clone(),
                     git_repo,
                     git_branch,
-                    input_tokens: 0,
-                    output_tokens: 0,
-                }
+                    git_worktree,
+                    status_since: Some(now),
+                    ..Default::default()
+                };
+                apply_naming(names, &mut session);
+                session
             });
 
         // Update fields if provided
-        if let Some(cwd) = &report.cwd {
-            if session.cwd.as_ref() != Some(cwd) {
-                session.cwd = Some(cwd.clone());
-                session.slug = slug_from_cwd(cwd);
-                session.git_repo = git_repo_from_cwd(cwd);
-                session.git_branch = git_branch_from_cwd(cwd);
-            }
+        if let Some(cwd) = &report.cwd
+            && session.cwd.as_ref() != Some(cwd)
+        {
+            session.cwd = Some(cwd.clone());
+            session.git_repo = git_repo_from_cwd(cwd);
+            session.git_branch = git_branch_from_cwd(cwd);
+            session.git_worktree = git_worktree_from_cwd(cwd);
+            apply_naming(names, session);
         }
         if report.tmux_pane.is_some() {
             session.tmux_pane = report.tmux_pane.clone();
+            session.mux = Some(report.mux.unwrap_or_default());
+        }
+        if report.claude_pid.is_some() {
+            session.pid = report.claude_pid;
+            session.tty = report.tty.clone();
+        }
+        if report.tool_name.is_some() {
+            session.last_tool = report.tool_name.clone();
+        }
+        if event == "Notification" && report.message.is_some() {
+            session.last_message = report.message.clone();
+        }
+        match event {
+            "PreToolUse" if report.tool_name.is_some() => {
+                session.current_tool = report.tool_name.clone();
+                session.tool_started = Some(now);
+            }
+            "PostToolUse" | "Stop" => {
+                session.current_tool = None;
+                session.tool_started = None;
+            }
+            _ => {}
+        }
+
+        // Subagents run inside the Task tool and each report SubagentStop when done;
+        // once the main agent stops, none can still be running
+        match event {
+            "PreToolUse" if report.tool_name.as_deref() == Some("Task") => {
+                session.subagents += 1;
+            }
+            "SubagentStop" => session.subagents = session.subagents.saturating_sub(1),
+            "Stop" => session.subagents = 0,
+            _ => {}
+        }
+
+        // Compaction has no end event; whatever hook fires next means it finished
+        let compaction = session.compacting.take();
+        if event == "PreCompact" {
+            session.compacting = Some(report.trigger.clone().unwrap_or_else(|| "auto".into()));
+            session.last_compaction = Some(now);
         }
 
         // State machine transitions
         let new_status = match event {
+            // After an automatic compaction the session carries on with its turn
+            "SessionStart" if report.source.as_deref() == Some("compact") => {
+                (compaction.as_deref() == Some("manual")).then_some(Status::Idle)
+            }
             "SessionStart" => Some(Status::Idle),
             "UserPromptSubmit" | "PreToolUse" => Some(Status::Working),
             "Stop" => Some(Status::Idle),
@@ -112,82 +366,207 @@ impl SessionMap {
                 }
             }
             "PermissionRequest" => Some(Status::Attention),
-            "PostToolUse" => Some(Status::Working),
+            "PostToolUse" | "PreCompact" => Some(Status::Working),
             _ => None,
         };
 
-        if let Some(status) = new_status {
+        if let Some(status) = new_status
+            && status != session.status
+        {
             session.status = status;
+            session.status_since = Some(now);
         }
         session.last_activity = now;
+        session.flag = None;
 
-        let mut msgs = Vec::new();
-        if let Some(rm) = polled_remove {
-            msgs.push(rm);
-        }
+        let mut msgs = polled_remove;
         msgs.push(ServerMessage::Update {
-            session: session.clone(),
+            session: Box::new(session.clone()),
         });
+        self.refresh_slugs(&mut msgs);
         msgs
     }
 
-    /// Register a session discovered by the poller.
+    /// Register a session discovered by the poller, either in a multiplexer pane or,
+    /// outside any, by its claude process.
     pub fn register_polled(
         &mut self,
         session_id: String,
         cwd: String,
-        tmux_pane: String,
-    ) -> Option<ServerMessage> {
+        pane: Option<(MuxKind, String)>,
+        pid: u32,
+        tty: Option<String>,
+    ) -> Vec<ServerMessage> {
+        let (mux, tmux_pane) = pane.unzip();
         if self.sessions.contains_key(&session_id) {
-            return None;
+            return Vec::new();
         }
 
-        // Don't create a polled session if a hook-reported session already owns this pane
-        let pane_taken = self.sessions.values().any(|s| {
-            !s.session_id.starts_with("polled-") && s.tmux_pane.as_ref() == Some(&tmux_pane)
+        // Don't create a polled session if a hook-reported session already owns this
+        // pane or process
+        let taken = self.sessions.values().any(|s| {
+            !s.session_id.starts_with("polled-")
+                && ((tmux_pane.is_some() && s.tmux_pane == tmux_pane) || s.pid == Some(pid))
         });
-        if pane_taken {
-            return None;
+        if taken {
+            return Vec::new();
         }
 
-        let slug = slug_from_cwd(&cwd);
         let git_repo = git_repo_from_cwd(&cwd);
         let git_branch = git_branch_from_cwd(&cwd);
-        let session = Session {
+        let git_worktree = git_worktree_from_cwd(&cwd);
+        let now = Utc::now();
+        let mut session = Session {
             session_id: session_id.clone(),
-            slug,
             status: Status::Idle,
-            last_activity: Utc::now(),
+            last_activity: now,
             cwd: Some(cwd),
-            tmux_pane: Some(tmux_pane),
+            tmux_pane,
+            mux,
+            pid: Some(pid),
+            tty,
             git_repo,
             git_branch,
-            input_tokens: 0,
-            output_tokens: 0,
+            git_worktree,
+            status_since: Some(now),
+            ..Default::default()
         };
+        apply_naming(&self.names, &mut session);
         self.sessions.insert(session_id, session.clone());
-        Some(ServerMessage::Update { session })
+        let mut msgs = vec![ServerMessage::Update {
+            session: Box::new(session),
+        }];
+        self.refresh_slugs(&mut msgs);
+        msgs
     }
 
-    /// Remove a session and return a Remove message if it existed.
-    pub fn remove(&mut self, session_id: &str) -> Option<ServerMessage> {
-        self.sessions.remove(session_id).map(|_| ServerMessage::Remove {
+    /// Remove a session along with its own name and labels, returning a Remove message
+    /// (plus slug updates for sessions that no longer need a longer form) if it existed.
+    pub fn remove(&mut self, session_id: &str) -> Vec<ServerMessage> {
+        self.names.forget_session(session_id);
+        self.history.remove(session_id);
+        self.pane_activity.remove(session_id);
+        if self.sessions.remove(session_id).is_none() {
+            return Vec::new();
+        }
+        let mut msgs = vec![ServerMessage::Remove {
             session_id: session_id.to_string(),
+        }];
+        self.refresh_slugs(&mut msgs);
+        msgs
+    }
+
+    /// Apply what a tmux pane capture taken at `captured_at` shows.
+    /// Pane tokens overwrite transcript-derived ones since they are more current. The
+    /// status is only inferred when no hook has arrived since the capture: hook-reported
+    /// sessions are only raised to Attention by a permission dialog and back to Working
+    /// by the spinner, while polled sessions, which get no hooks, follow the pane fully.
+    /// Returns an Update message only when something changed.
+    pub fn apply_pane(
+        &mut self,
+        session_id: &str,
+        pane: &PaneSnapshot,
+        captured_at: DateTime<Utc>,
+    ) -> Option<ServerMessage> {
+        let session = self.sessions.get_mut(session_id)?;
+        let before = session.clone();
+
+        if let Some(tokens) = pane.tokens.filter(|&t| t > 0) {
+            session.input_tokens = tokens;
+            session.output_tokens = 0;
+        }
+        if pane.model.is_some() {
+            session.model = pane.model.clone();
+        }
+        session.context_left = pane.context_left;
+        if pane.cost_usd.is_some() {
+            session.cost_usd = pane.cost_usd;
+        }
+
+        if session.last_activity <= captured_at {
+            let polled = session.session_id.starts_with("polled-");
+            let status = match (pane.permission_prompt, pane.working, session.status) {
+                (true, _, _) => Status::Attention,
+                (false, true, _) => Status::Working,
+                (false, false, _) if polled => Status::Idle,
+                (false, false, current) => current,
+            };
+            if status != session.status {
+                session.status = status;
+                session.status_since = Some(captured_at);
+                session.flag = None;
+            }
+        }
+
+        let changed = session.input_tokens != before.input_tokens
+            || session.model != before.model
+            || session.context_left != before.context_left
+            || session.cost_usd != before.cost_usd
+            || session.status != before.status;
+        changed.then(|| ServerMessage::Update {
+            session: Box::new(session.clone()),
         })
     }
 
-    /// Update token count from a live tmux pane capture.
-    /// Overwrites transcript-derived tokens since pane data is more current.
-    /// Returns an Update message only when the value changed.
-    pub fn update_pane_tokens(&mut self, session_id: &str, tokens: u64) -> Option<ServerMessage> {
+    /// Record a fingerprint of the session's pane content. A change counts as progress
+    /// and clears a stalled flag.
+    pub fn observe_pane(&mut self, session_id: &str, fingerprint: u64) -> Option<ServerMessage> {
+        let now = Utc::now();
+        let entry = self
+            .pane_activity
+            .entry(session_id.to_string())
+            .or_insert((fingerprint, now));
+        if entry.0 == fingerprint {
+            return None;
+        }
+        *entry = (fingerprint, now);
+
         let session = self.sessions.get_mut(session_id)?;
-        let current = session.input_tokens + session.output_tokens;
-        if tokens == 0 || tokens == current {
+        if session.flag != Some(SessionFlag::Stalled) {
             return None;
         }
-        session.input_tokens = tokens;
-        session.output_tokens = 0;
-        Some(ServerMessage::Update { session: session.clone() })
+        session.flag = None;
+        Some(ServerMessage::Update {
+            session: Box::new(session.clone()),
+        })
+    }
+
+    /// Raise or clear stalled/forgotten flags against the given limits (`None` = never).
+    /// Polled sessions are never forgotten since their status isn't really known.
+    pub fn evaluate_flags(
+        &mut self,
+        now: DateTime<Utc>,
+        stalled_after: Option<Duration>,
+        forgotten_after: Option<Duration>,
+    ) -> Vec<ServerMessage> {
+        let mut msgs = Vec::new();
+        for session in self.sessions.values_mut() {
+            let flag = match session.status {
+                Status::Working => {
+                    let pane_change = self.pane_activity.get(&session.session_id).map(|a| a.1);
+                    let progress = pane_change.map_or(session.last_activity, |t| {
+                        t.max(session.last_activity)
+                    });
+                    stalled_after
+                        .filter(|limit| now - progress > *limit)
+                        .map(|_| SessionFlag::Stalled)
+                }
+                Status::Idle if !session.session_id.starts_with("polled-") => {
+                    let since = session.status_since.unwrap_or(session.last_activity);
+                    forgotten_after
+                        .filter(|limit| now - since > *limit)
+                        .map(|_| SessionFlag::Forgotten)
+                }
+                _ => None,
+            };
+            if session.flag != flag {
+                session.flag = flag;
+                msgs.push(ServerMessage::Update {
+                    session: Box::new(session.clone()),
+                });
+            }
+        }
+        msgs
     }
 
     /// Get all known tmux panes mapped to their session_ids.
@@ -201,5 +580,20 @@ impl SessionMap {
             })
             .collect()
     }
+
+    /// Sessions outside tmux mapped from their claude process id to their session_id.
+    pub fn pid_session_map(&self) -> HashMap<u32, String> {
+        self.sessions
+            .values()
+            .filter(|s| s.tmux_pane.is_none())
+            .filter_map(|s| s.pid.map(|pid| (pid, s.session_id.clone())))
+            .collect()
+    }
 }
 
+/// Set a session's name and labels from the store; `refresh_slugs` turns them into a slug.
+fn apply_naming(names: &NameStore, session: &mut Session) {
+    let Naming { name, labels } = names.lookup(session);
+    session.display_name = name;
+    session.labels = labels.unwrap_or_default();
+}
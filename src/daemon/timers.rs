@@ -0,0 +1,37 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use tokio::sync::{Mutex, broadcast};
+use tokio::time::{Duration, interval};
+
+use crate::protocol::ServerMessage;
+
+use super::state::SessionMap;
+
+/// How often the stalled/forgotten timers are checked.
+const CHECK_EVERY: Duration = Duration::from_secs(30);
+
+/// Periodically flag sessions that look stuck or abandoned, and unflag them once they
+/// are back within limits.
+pub async fn flag_loop(
+    state: Arc<Mutex<SessionMap>>,
+    tx: broadcast::Sender<ServerMessage>,
+    stalled_after_secs: u64,
+    forgotten_after_secs: u64,
+) {
+    let limit = |secs: u64| (secs > 0).then(|| chrono::Duration::seconds(secs as i64));
+    let stalled_after = limit(stalled_after_secs);
+    let forgotten_after = limit(forgotten_after_secs);
+    if stalled_after.is_none() && forgotten_after.is_none() {
+        return;
+    }
+
+    let mut ticker = interval(CHECK_EVERY);
+    loop {
+        ticker.tick().await;
+        let mut map = state.lock().await;
+        for msg in map.evaluate_flags(Utc::now(), stalled_after, forgotten_after) {
+            let _ = tx.send(msg);
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+
+use tokio::sync::{Mutex, broadcast};
+
+use crate::protocol::ServerMessage;
+use crate::session::{Session, SessionFlag, Status};
+
+use super::state::SessionMap;
+
+/// A session's status changed, or it was newly flagged, between two broadcasts.
+pub struct Transition {
+    pub session: Session,
+    /// `None` when the session was first seen with this status.
+    pub from: Option<Status>,
+    pub to: Status,
+    /// The flag this update raised, if any.
+    pub flagged: Option<SessionFlag>,
+}
+
+impl Transition {
+    pub fn status_changed(&self) -> bool {
+        self.from != Some(self.to)
+    }
+}
+
+/// Remembers the last broadcast status and flag of every session so subscribers that only
+/// see `ServerMessage`s can tell real changes from token or metadata updates.
+#[derive(Default)]
+pub struct StatusTracker {
+    last: HashMap<String, (Status, Option<SessionFlag>)>,
+}
+
+impl StatusTracker {
+    /// Reset to the given sessions without reporting any transitions.
+    pub fn seed(&mut self, sessions: &[Session]) {
+        self.last = sessions
+            .iter()
+            .map(|s| (s.session_id.clone(), (s.status, s.flag)))
+            .collect();
+    }
+
+    /// Feed one broadcast message; returns a transition if a session's status changed
+    /// or it gained a flag.
+    pub fn observe(&mut self, msg: &ServerMessage) -> Option<Transition> {
+        match msg {
+            ServerMessage::Update { session } => {
+                let prev = self
+                    .last
+                    .insert(session.session_id.clone(), (session.status, session.flag));
+                let from = prev.map(|(status, _)| status);
+                let flagged = session
+                    .flag
+                    .filter(|_| prev.map(|(_, flag)| flag) != Some(session.flag));
+                if from == Some(session.status) && flagged.is_none() {
+                    return None;
+                }
+                Some(Transition {
+                    session: (**session).clone(),
+                    from,
+                    to: session.status,
+                    flagged,
+                })
+            }
+            ServerMessage::Remove { session_id } => {
+                self.last.remove(session_id);
+                None
+            }
+            ServerMessage::Snapshot { sessions } => {
+                self.seed(sessions);
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Call `on_transition` for every transition on the broadcast channel until it closes.
+/// A lagged receiver re-seeds from the session map, so missed changes are dropped rather
+/// than reported late.
+pub async fn watch_transitions(
+    state: &Mutex<SessionMap>,
+    tx: &broadcast::Sender<ServerMessage>,
+    mut on_transition: impl FnMut(Transition),
+) {
+    let mut rx = tx.subscribe();
+    let mut tracker = StatusTracker::default();
+    tracker.seed(&state.lock().await.sessions());
+
+    loop {
+        let msg = match rx.recv().await {
+            Ok(msg) => msg,
+            Err(broadcast::error::RecvError::Lagged(_)) => {
+                tracker.seed(&state.lock().await.sessions());
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        if let Some(transition) = tracker.observe(&msg) {
+            on_transition(transition);
+        }
+    }
+}
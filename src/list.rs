@@ -0,0 +1,61 @@
+use std::path::Path;
+
+use chrono::Utc;
+
+use crate::client::{Filter, fetch_snapshot, matches_all};
+use crate::session::{format_tokens, relative_time, sort_sessions};
+
+/// Print the current sessions once and exit.
+pub fn run(socket: &Path, json: bool, filters: &[Filter]) {
+    if let Err(e) = run_inner(socket, json, filters) {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
+}
+
+fn run_inner(socket: &Path, json: bool, filters: &[Filter]) -> anyhow::Result<()> {
+    let mut sessions = fetch_snapshot(socket)?;
+    sort_sessions(&mut sessions);
+    sessions.retain(|s| matches_all(filters, s));
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&sessions)?);
+        return Ok(());
+    }
+
+    let now = Utc::now();
+    let rows: Vec<[String; 6]> = sessions
+        .iter()
+        .map(|s| {
+            [
+                s.slug.clone(),
+                match s.flag {
+                    Some(flag) => format!("{} ({})", s.status.label(), flag.label()),
+                    None => s.status.label().to_string(),
+                },
+                format_tokens(s.input_tokens + s.output_tokens),
+                relative_time(s.last_activity, now),
+                s.git_branch.clone().unwrap_or_default(),
+                s.labels.join(","),
+            ]
+        })
+        .collect();
+
+    let header = ["SESSION", "STATUS", "TOKENS", "ACTIVITY", "BRANCH", "LABELS"].map(String::from);
+    let mut widths = header.clone().map(|h| h.chars().count());
+    for row in &rows {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+
+    for row in std::iter::once(&header).chain(&rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, w)| format!("{cell:<w$}"))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    }
+    Ok(())
+}
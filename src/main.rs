@@ -1,14 +1,34 @@
+mod client;
+mod config;
+mod ctl;
 mod daemon;
+mod list;
+mod mux;
+mod procs;
 mod protocol;
 mod report;
+mod send;
 mod session;
+mod tmux_status;
 mod tui;
+mod watch;
+
+use std::path::PathBuf;
+use std::time::Duration;
 
 use clap::{Parser, Subcommand};
 
 #[derive(Parser)]
 #[command(name = "bottycall", about = "Claude Code session monitor")]
 struct Cli {
+    /// Daemon socket path (defaults to $XDG_RUNTIME_DIR/bottycall.sock)
+    #[arg(long, global = true, env = "BOTTYCALL_SOCKET")]
+    socket: Option<PathBuf>,
+
+    /// Config file path (defaults to ~/.config/bottycall/config.toml)
+    #[arg(long, global = true, env = "BOTTYCALL_CONFIG")]
+    config: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Command,
 }
@@ -16,7 +36,17 @@ struct Cli {
 #[derive(Subcommand)]
 enum Command {
     /// Start the monitoring daemon
-    Daemon,
+    Daemon {
+        /// Seconds between tmux/process scans
+        #[arg(long)]
+        poll_interval: Option<u64>,
+        /// Seconds between pane captures for live token counts
+        #[arg(long)]
+        token_poll_interval: Option<u64>,
+        /// Process name identifying a Claude Code session
+        #[arg(long)]
+        process_name: Option<String>,
+    },
     /// Report a hook event (called by Claude Code hooks, reads stdin)
     Report {
         /// The hook event name (e.g. SessionStart, Stop, PreToolUse)
@@ -25,32 +55,126 @@ enum Command {
     },
     /// Launch the interactive TUI dashboard
     Tui,
+    /// Print the current sessions and exit
+    #[command(alias = "status")]
+    List {
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+        /// Only show matching sessions, e.g. status=attention or repo=crate (repeatable)
+        #[arg(long = "filter", value_name = "KEY=VALUE")]
+        filters: Vec<client::Filter>,
+    },
+    /// Stream session changes as they happen
+    Watch {
+        /// Print raw NDJSON protocol messages instead of human-readable lines
+        #[arg(long)]
+        json: bool,
+        /// Only show matching sessions, e.g. status=attention or repo=crate (repeatable)
+        #[arg(long = "filter", value_name = "KEY=VALUE")]
+        filters: Vec<client::Filter>,
+    },
+    /// Send a control command to the daemon
+    Ctl {
+        /// Print the raw JSON reply
+        #[arg(long, global = true)]
+        json: bool,
+        #[command(subcommand)]
+        command: ctl::CtlCommand,
+    },
+    /// Type a prompt into a session's pane and submit it
+    Send {
+        /// Session id, unique id prefix or slug
+        session: String,
+        /// Prompt text; read from stdin when omitted
+        text: Option<String>,
+    },
+    /// Print a compact status summary for tmux's status-right
+    ///
+    /// Format placeholders: {attention}, {working}, {idle} and {total} expand to counts;
+    /// {working?TEXT} expands to TEXT plus the count, or nothing when the count is zero.
+    /// TEXT may contain tmux styles such as #[fg=yellow].
+    TmuxStatus {
+        /// Format string (defaults to the [tmux] config format)
+        #[arg(long)]
+        format: Option<String>,
+        /// Seconds to reuse a cached snapshot
+        #[arg(long)]
+        max_age: Option<u64>,
+    },
 }
 
 fn main() {
     let cli = Cli::parse();
 
+    let config_path = cli.config.unwrap_or_else(config::default_config_path);
+    let mut config = match config::load(&config_path) {
+        Ok(config) => config,
+        // The hook reporter must never fail loudly; fall back to defaults
+        Err(_) if matches!(cli.command, Command::Report { .. }) => config::Config::default(),
+        Err(e) => {
+            eprintln!("error: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    let socket = cli
+        .socket
+        .or(config.socket.take())
+        .unwrap_or_else(protocol::default_socket_path);
+
     match cli.command {
-        Command::Daemon => {
+        Command::Daemon {
+            poll_interval,
+            token_poll_interval,
+            process_name,
+        } => {
+            let daemon_config = &mut config.daemon;
+            if let Some(secs) = poll_interval {
+                daemon_config.poll_interval_secs = secs;
+            }
+            if let Some(secs) = token_poll_interval {
+                daemon_config.token_poll_interval_secs = secs;
+            }
+            if let Some(name) = process_name {
+                daemon_config.process_name = name;
+            }
+
             // Ensure Homebrew / common bin dirs are in PATH so the poller
             // can find tmux and git when launched via launchd.
             let path = std::env::var("PATH").unwrap_or_default();
+            let mut dirs = daemon_config.path_prefix.clone();
+            dirs.push(path);
             unsafe {
-                std::env::set_var(
-                    "PATH",
-                    format!("/opt/homebrew/bin:/usr/local/bin:{path}"),
-                );
+                std::env::set_var("PATH", dirs.join(":"));
             }
 
             let rt = tokio::runtime::Runtime::new().expect("failed to create tokio runtime");
-            rt.block_on(daemon::run());
+            rt.block_on(daemon::run(&socket, config));
         }
         Command::Report { event } => {
-            report::run(&event);
+            report::run(&event, &socket, &config.daemon.process_name);
         }
         Command::Tui => {
             let rt = tokio::runtime::Runtime::new().expect("failed to create tokio runtime");
-            rt.block_on(tui::run());
+            rt.block_on(tui::run(&socket, &config.tui));
+        }
+        Command::List { json, filters } => {
+            list::run(&socket, json, &filters);
+        }
+        Command::Watch { json, filters } => {
+            watch::run(&socket, json, &filters);
+        }
+        Command::Ctl { json, command } => {
+            ctl::run(&socket, command, json);
+        }
+        Command::Send { session, text } => {
+            send::run(&socket, session, text);
+        }
+        Command::TmuxStatus { format, max_age } => {
+            let format = format.unwrap_or(config.tmux.format);
+            let max_age = Duration::from_secs(max_age.unwrap_or(config.tmux.cache_secs));
+            tmux_status::run(&socket, &format, max_age);
         }
     }
 }
@@ -0,0 +1,489 @@
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::procs::{ProcessInfo, ProcessSource};
+
+/// Terminal multiplexers sessions can run in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MuxKind {
+    #[default]
+    Tmux,
+    Screen,
+    Zellij,
+}
+
+impl MuxKind {
+    pub const ALL: [MuxKind; 3] = [MuxKind::Tmux, MuxKind::Screen, MuxKind::Zellij];
+
+    pub fn backend(self) -> &'static dyn Multiplexer {
+        match self {
+            MuxKind::Tmux => &Tmux,
+            MuxKind::Screen => &Screen,
+            MuxKind::Zellij => &Zellij,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MuxKind::Tmux => "tmux",
+            MuxKind::Screen => "screen",
+            MuxKind::Zellij => "zellij",
+        }
+    }
+}
+
+/// The multiplexer pane this process runs in, from the variables each multiplexer sets:
+/// `TMUX_PANE`, `STY` and `WINDOW` for screen, `ZELLIJ_SESSION_NAME` and `ZELLIJ_PANE_ID`.
+pub fn pane_from_env() -> Option<(MuxKind, String)> {
+    let var = |name| std::env::var(name).ok().filter(|v: &String| !v.is_empty());
+    if let Some(pane) = var("TMUX_PANE") {
+        return Some((MuxKind::Tmux, pane));
+    }
+    if let (Some(sty), Some(window)) = (var("STY"), var("WINDOW")) {
+        return Some((MuxKind::Screen, format!("{sty}:{window}")));
+    }
+    if let (Some(session), Some(pane)) = (var("ZELLIJ_SESSION_NAME"), var("ZELLIJ_PANE_ID")) {
+        return Some((MuxKind::Zellij, format!("{session}:{pane}")));
+    }
+    None
+}
+
+/// One pane and the pid of the process it was started with.
+pub struct MuxPane {
+    /// tmux `%12`, screen `<session>:<window>`, zellij `<session>:<pane id>`.
+    pub pane_id: String,
+    pub pid: u32,
+    pub cwd: String,
+}
+
+/// A key sent to a pane.
+pub enum Key<'a> {
+    /// Typed literally.
+    Text(&'a str),
+    Enter,
+    Escape,
+}
+
+/// A terminal multiplexer the daemon can find panes in and the TUI can drive. Calls block,
+/// so run them off the async executor.
+pub trait Multiplexer: Send + Sync {
+    /// Every pane, or `None` if the multiplexer can't be asked. `table` is the process
+    /// table the caller already read from `processes`.
+    fn list_panes(
+        &self,
+        processes: &dyn ProcessSource,
+        table: &[ProcessInfo],
+    ) -> Option<Vec<MuxPane>>;
+    /// The visible text of a pane.
+    fn capture_pane(&self, pane_id: &str) -> Option<String>;
+    /// Bring a pane to the front of the client it is shown in.
+    fn focus_pane(&self, pane_id: &str) -> anyhow::Result<()>;
+    /// Type keys into a pane.
+    fn send_keys(&self, pane_id: &str, keys: &[Key]) -> anyhow::Result<()>;
+    /// Insert text into a pane as one paste, so its newlines don't submit it line by line.
+    fn paste(&self, pane_id: &str, text: &str) -> anyhow::Result<()>;
+}
+
+pub struct Tmux;
+
+impl Multiplexer for Tmux {
+    fn list_panes(
+        &self,
+        _processes: &dyn ProcessSource,
+        _table: &[ProcessInfo],
+    ) -> Option<Vec<MuxPane>> {
+        let output = Command::new("tmux")
+            .args([
+                "list-panes",
+                "-a",
+                "-F",
+                "#{pane_id} #{pane_pid} #{pane_current_path}",
+            ])
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            // Without a server every tmux pane is gone; any other failure (a broken
+            // binary, a server that won't answer) says nothing about the panes
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return tmux_no_server(&stderr).then(Vec::new);
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+
+        let panes = stdout
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.splitn(3, ' ');
+                let pane_id = parts.next()?.to_string();
+                let pid: u32 = parts.next()?.parse().ok()?;
+                let cwd = parts.next()?.to_string();
+                Some(MuxPane { pane_id, pid, cwd })
+            })
+            .collect();
+
+        Some(panes)
+    }
+
+    fn capture_pane(&self, pane_id: &str) -> Option<String> {
+        output(Command::new("tmux").args(["capture-pane", "-t", pane_id, "-p"]))
+    }
+
+    fn focus_pane(&self, pane_id: &str) -> anyhow::Result<()> {
+        // switch-client handles cross-session jumps (select-pane/select-window don't)
+        run(Command::new("tmux").args(["switch-client", "-t", pane_id]))
+    }
+
+    fn send_keys(&self, pane_id: &str, keys: &[Key]) -> anyhow::Result<()> {
+        for key in keys {
+            let mut cmd = Command::new("tmux");
+            cmd.args(["send-keys", "-t", pane_id]);
+            match key {
+                Key::Text(text) => cmd.args(["-l", text]),
+                Key::Enter => cmd.arg("Enter"),
+                Key::Escape => cmd.arg("Escape"),
+            };
+            run(&mut cmd)?;
+        }
+        Ok(())
+    }
+
+    fn paste(&self, pane_id: &str, text: &str) -> anyhow::Result<()> {
+        // A private buffer leaves the user's paste buffers alone; -d drops it afterwards,
+        // -p pastes in bracketed mode when the pane's application asked for it, and -r
+        // keeps newlines as LF instead of turning them into CR, which would submit
+        let buffer = format!("bottycall-{}-{}", std::process::id(), next_id());
+        run_with_stdin(
+            Command::new("tmux").args(["load-buffer", "-b", &buffer, "-"]),
+            text.as_bytes(),
+        )?;
+        run(Command::new("tmux").args([
+            "paste-buffer",
+            "-p",
+            "-r",
+            "-d",
+            "-b",
+            &buffer,
+            "-t",
+            pane_id,
+        ]))
+    }
+}
+
+/// Whether tmux failed because no server is running: the socket is missing, or left
+/// behind by a server that exited.
+fn tmux_no_server(stderr: &str) -> bool {
+    stderr.contains("no server running")
+        || (stderr.starts_with("error connecting to")
+            && (stderr.contains("No such file or directory")
+                || stderr.contains("Connection refused")))
+}
+
+/// GNU screen. Every window is a pane; windows are found as children of the screen server
+/// carrying `STY` and `WINDOW` in their environment.
+pub struct Screen;
+
+impl Multiplexer for Screen {
+    fn list_panes(
+        &self,
+        processes: &dyn ProcessSource,
+        table: &[ProcessInfo],
+    ) -> Option<Vec<MuxPane>> {
+        Some(panes_from_env(processes, table, "screen", "STY", "WINDOW"))
+    }
+
+    fn capture_pane(&self, pane_id: &str) -> Option<String> {
+        let (session, window) = split_pane(pane_id).ok()?;
+        let path = scratch_path();
+        let written = run(Command::new("screen")
+            .args(["-S", session, "-p", window, "-X", "hardcopy"])
+            .arg(&path));
+        // The server writes the file after the client has returned
+        let content = written.ok().and_then(|()| read_when_ready(&path));
+        let _ = std::fs::remove_file(&path);
+        content
+    }
+
+    fn focus_pane(&self, pane_id: &str) -> anyhow::Result<()> {
+        let (session, window) = split_pane(pane_id)?;
+        run(Command::new("screen").args(["-S", session, "-X", "select", window]))
+    }
+
+    fn send_keys(&self, pane_id: &str, keys: &[Key]) -> anyhow::Result<()> {
+        let (session, window) = split_pane(pane_id)?;
+        for key in keys {
+            let text = match key {
+                Key::Text(text) => escape_screen(text),
+                Key::Enter => "\r".to_string(),
+                Key::Escape => "\x1b".to_string(),
+            };
+            run(Command::new("screen")
+                .args(["-S", session, "-p", window, "-X", "stuff", &text]))?;
+        }
+        Ok(())
+    }
+
+    fn paste(&self, pane_id: &str, text: &str) -> anyhow::Result<()> {
+        self.send_keys(pane_id, &[Key::Text(&bracketed(text))])
+    }
+}
+
+/// screen parses `stuff` arguments like its config file: protect backslashes, `^` and `$`.
+fn escape_screen(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '^' | '$') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Focus steps tried before giving up on reaching a Zellij pane.
+const ZELLIJ_MAX_FOCUS_STEPS: usize = 64;
+
+/// Zellij. Panes are found like screen's, from `ZELLIJ_SESSION_NAME` and `ZELLIJ_PANE_ID`.
+/// Zellij's CLI acts on the focused pane only, so capturing requires the pane to be
+/// focused, and focusing and sending keys walk the focus through the session's panes.
+pub struct Zellij;
+
+impl Zellij {
+    /// Pane ids focused by the session's clients, from `zellij action list-clients`
+    /// (`CLIENT_ID ZELLIJ_PANE_ID RUNNING_COMMAND`, e.g. `1 terminal_3 claude`).
+    fn focused(&self, session: &str) -> Vec<String> {
+        output(Command::new("zellij").args(["--session", session, "action", "list-clients"]))
+            .map(|stdout| {
+                stdout
+                    .lines()
+                    .skip(1)
+                    .filter_map(|l| l.split_whitespace().nth(1)?.strip_prefix("terminal_"))
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Undo a `focus_pane` walk that gave up part-way: cycle the current tab back to the
+    /// pane it started on, then step back over the tabs that were left. Best effort.
+    fn restore_focus(&self, session: &str, tab_start: Option<&str>, tabs_moved: usize) {
+        for _ in 0..ZELLIJ_MAX_FOCUS_STEPS {
+            let current = self.focused(session).into_iter().next();
+            if current.as_deref() == tab_start
+                || self.action(session, &["focus-next-pane"]).is_err()
+            {
+                break;
+            }
+        }
+        for _ in 0..tabs_moved {
+            if self.action(session, &["go-to-previous-tab"]).is_err() {
+                break;
+            }
+        }
+    }
+
+    fn action(&self, session: &str, args: &[&str]) -> anyhow::Result<()> {
+        run(Command::new("zellij").args(["--session", session, "action"]).args(args))
+    }
+}
+
+impl Multiplexer for Zellij {
+    fn list_panes(
+        &self,
+        processes: &dyn ProcessSource,
+        table: &[ProcessInfo],
+    ) -> Option<Vec<MuxPane>> {
+        Some(panes_from_env(processes, table, "zellij", "ZELLIJ_SESSION_NAME", "ZELLIJ_PANE_ID"))
+    }
+
+    fn capture_pane(&self, pane_id: &str) -> Option<String> {
+        let (session, pane) = split_pane(pane_id).ok()?;
+        if !self.focused(session).iter().any(|p| p == pane) {
+            return None;
+        }
+        let path = scratch_path();
+        let dumped = self.action(session, &["dump-screen", path.to_str()?]);
+        let content = dumped.ok().and_then(|()| read_when_ready(&path));
+        let _ = std::fs::remove_file(&path);
+        content
+    }
+
+    fn focus_pane(&self, pane_id: &str) -> anyhow::Result<()> {
+        let (session, pane) = split_pane(pane_id)?;
+        let focused = || self.focused(session).into_iter().next();
+        let start = focused();
+        let mut tab_start = start.clone();
+        let mut tabs_moved = 0;
+        let mut walk = || -> anyhow::Result<bool> {
+            for _ in 0..ZELLIJ_MAX_FOCUS_STEPS {
+                if focused().as_deref() == Some(pane) {
+                    return Ok(true);
+                }
+                self.action(session, &["focus-next-pane"])?;
+                // Back where this tab started: every pane in it has been tried
+                if focused() == tab_start {
+                    self.action(session, &["go-to-next-tab"])?;
+                    tabs_moved += 1;
+                    tab_start = focused();
+                    if tab_start == start {
+                        // Every tab tried; each was left on the pane it started on
+                        tabs_moved = 0;
+                        return Ok(false);
+                    }
+                }
+            }
+            Ok(false)
+        };
+        let found = walk();
+        if !matches!(found, Ok(true)) {
+            self.restore_focus(session, tab_start.as_deref(), tabs_moved);
+        }
+        anyhow::ensure!(found?, "pane {pane} not found in zellij session {session}");
+        Ok(())
+    }
+
+    fn send_keys(&self, pane_id: &str, keys: &[Key]) -> anyhow::Result<()> {
+        let (session, _) = split_pane(pane_id)?;
+        self.focus_pane(pane_id)?;
+        for key in keys {
+            match key {
+                Key::Text(text) => self.action(session, &["write-chars", text])?,
+                Key::Enter => self.action(session, &["write", "13"])?,
+                Key::Escape => self.action(session, &["write", "27"])?,
+            }
+        }
+        Ok(())
+    }
+
+    fn paste(&self, pane_id: &str, text: &str) -> anyhow::Result<()> {
+        self.send_keys(pane_id, &[Key::Text(&bracketed(text))])
+    }
+}
+
+/// Wrap text in bracketed-paste markers for multiplexers without a paste command; Claude
+/// Code enables bracketed paste, so it takes the text as one paste rather than keystrokes.
+fn bracketed(text: &str) -> String {
+    format!("\x1b[200~{text}\x1b[201~")
+}
+
+/// Panes of a multiplexer that tags its panes' processes with a session and a pane
+/// variable: the direct children of its server processes named `server`.
+fn panes_from_env(
+    processes: &dyn ProcessSource,
+    table: &[ProcessInfo],
+    server: &str,
+    session_var: &str,
+    pane_var: &str,
+) -> Vec<MuxPane> {
+    let servers: HashSet<u32> = table
+        .iter()
+        .filter(|e| e.comm == server)
+        .map(|e| e.pid)
+        .collect();
+    if servers.is_empty() {
+        return Vec::new();
+    }
+
+    table
+        .iter()
+        .filter(|e| e.comm != server && servers.contains(&e.ppid))
+        .filter_map(|e| {
+            let vars = processes.env_vars(e.pid, &[session_var, pane_var])?;
+            let [session, pane] = vars.as_slice() else {
+                return None;
+            };
+            Some(MuxPane {
+                pane_id: format!("{session}:{pane}"),
+                pid: e.pid,
+                cwd: processes.cwd(e.pid).unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+/// Split a `<session>:<pane>` id; session names may themselves contain colons.
+fn split_pane(pane_id: &str) -> anyhow::Result<(&str, &str)> {
+    pane_id
+        .rsplit_once(':')
+        .ok_or_else(|| anyhow::anyhow!("invalid pane id {pane_id:?}"))
+}
+
+/// A number unique within this process, for naming scratch files and buffers.
+fn next_id() -> u32 {
+    static NEXT: AtomicU32 = AtomicU32::new(0);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+/// A fresh temporary file path for screen and zellij to dump panes into.
+fn scratch_path() -> PathBuf {
+    std::env::temp_dir().join(format!("bottycall-{}-{}.txt", std::process::id(), next_id()))
+}
+
+/// Read a file written by another process, waiting briefly for it to appear.
+fn read_when_ready(path: &Path) -> Option<String> {
+    for _ in 0..10 {
+        if let Ok(bytes) = std::fs::read(path) {
+            return Some(String::from_utf8_lossy(&bytes).into_owned());
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    None
+}
+
+/// Run a command and return its stdout if it succeeded.
+fn output(cmd: &mut Command) -> Option<String> {
+    let output = cmd.output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Run a command with its output discarded, failing on a non-zero exit.
+fn run(cmd: &mut Command) -> anyhow::Result<()> {
+    check(cmd.output()?)
+}
+
+/// Run a command with `input` on stdin, failing on a non-zero exit.
+fn run_with_stdin(cmd: &mut Command, input: &[u8]) -> anyhow::Result<()> {
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input)?;
+    }
+    check(child.wait_with_output()?)
+}
+
+fn check(output: Output) -> anyhow::Result<()> {
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    match stderr.trim() {
+        "" => anyhow::bail!("exited with {}", output.status),
+        message => anyhow::bail!("{message}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tmux_without_server() {
+        assert!(tmux_no_server("no server running on /tmp/tmux-1000/default\n"));
+        assert!(tmux_no_server(
+            "error connecting to /tmp/tmux-1000/default (No such file or directory)\n"
+        ));
+        assert!(!tmux_no_server("unknown option -- Z\n"));
+        assert!(!tmux_no_server("error connecting to /tmp/tmux-1000/default (Permission denied)\n"));
+    }
+}
@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+use std::sync::Arc;
+
+/// One entry of the process table.
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub ppid: u32,
+    /// Binary name, without path or the leading `-` of login shells.
+    pub comm: String,
+    /// Controlling terminal, e.g. `pts/3` or `ttys003`; `None` for daemons and pipelines.
+    pub tty: Option<String>,
+}
+
+/// Somewhere to read the process table from. Calls block, so run them off the async
+/// executor.
+pub trait ProcessSource: Send + Sync {
+    /// Every process, or `None` if the table can't be read.
+    fn processes(&self) -> Option<Vec<ProcessInfo>>;
+    /// A single process, if it exists.
+    fn process(&self, pid: u32) -> Option<ProcessInfo>;
+    /// The working directory of a process, if it can be read.
+    fn cwd(&self, pid: u32) -> Option<String>;
+    /// Variables from the environment a process was started with, in the order of `names`,
+    /// read in one go. `None` unless every one of them is set.
+    fn env_vars(&self, pid: u32, names: &[&str]) -> Option<Vec<String>>;
+
+    /// `pid` followed by up to `max_hops` of its ancestors, nearest first.
+    fn ancestry(&self, pid: u32, max_hops: usize) -> Vec<ProcessInfo> {
+        walk_ancestry(pid, max_hops, |pid| self.process(pid))
+    }
+}
+
+/// Follow parent links from `pid`, stopping at init, a self-parented process or a pid
+/// that can't be looked up.
+fn walk_ancestry(
+    pid: u32,
+    max_hops: usize,
+    mut lookup: impl FnMut(u32) -> Option<ProcessInfo>,
+) -> Vec<ProcessInfo> {
+    let mut chain: Vec<ProcessInfo> = lookup(pid).into_iter().collect();
+    while let Some(last) = chain.last()
+        && chain.len() <= max_hops
+        && last.ppid != 0
+        && last.ppid != last.pid
+    {
+        let Some(parent) = lookup(last.ppid) else {
+            break;
+        };
+        chain.push(parent);
+    }
+    chain
+}
+
+/// `/proc` where it exists (Linux), otherwise `ps` and `lsof`.
+pub fn detect() -> Arc<dyn ProcessSource> {
+    if Path::new("/proc/self/stat").exists() {
+        Arc::new(ProcFs)
+    } else {
+        Arc::new(Ps)
+    }
+}
+
+/// Reads `/proc/<pid>/stat` and `/proc/<pid>/cwd` directly.
+pub struct ProcFs;
+
+impl ProcessSource for ProcFs {
+    fn processes(&self) -> Option<Vec<ProcessInfo>> {
+        let entries = std::fs::read_dir("/proc").ok()?;
+        Some(
+            entries
+                .filter_map(|e| e.ok()?.file_name().to_str()?.parse::<u32>().ok())
+                // Processes can exit between listing and reading; just skip them
+                .filter_map(read_proc_stat)
+                .collect(),
+        )
+    }
+
+    fn process(&self, pid: u32) -> Option<ProcessInfo> {
+        read_proc_stat(pid)
+    }
+
+    fn cwd(&self, pid: u32) -> Option<String> {
+        let path = std::fs::read_link(format!("/proc/{pid}/cwd")).ok()?;
+        path.to_str().map(str::to_string)
+    }
+
+    fn env_vars(&self, pid: u32, names: &[&str]) -> Option<Vec<String>> {
+        let environ = std::fs::read(format!("/proc/{pid}/environ")).ok()?;
+        names
+            .iter()
+            .map(|name| {
+                environ.split(|&b| b == 0).find_map(|entry| {
+                    let value = entry.strip_prefix(name.as_bytes())?.strip_prefix(b"=")?;
+                    Some(String::from_utf8_lossy(value).into_owned())
+                })
+            })
+            .collect()
+    }
+}
+
+/// Read one process from `/proc/<pid>/stat`, or `None` if it has exited.
+fn read_proc_stat(pid: u32) -> Option<ProcessInfo> {
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    let (comm, ppid, tty_nr) = parse_proc_stat(&stat)?;
+    Some(ProcessInfo {
+        pid,
+        ppid,
+        comm: full_comm(pid, comm),
+        tty: tty_name(tty_nr),
+    })
+}
+
+/// Parse `/proc/<pid>/stat`: `pid (comm) state ppid pgrp session tty_nr ...`. `comm` may
+/// itself contain spaces and parentheses, so split at the last `)`.
+fn parse_proc_stat(stat: &str) -> Option<(&str, u32, u32)> {
+    let open = stat.find('(')?;
+    let close = stat.rfind(')')?;
+    let comm = &stat[open + 1..close];
+    let mut fields = stat[close + 1..].split_whitespace();
+    let _state = fields.next()?;
+    let ppid = fields.next()?.parse().ok()?;
+    let tty_nr = fields.nth(2)?.parse().ok()?;
+    Some((comm, ppid, tty_nr))
+}
+
+/// Name a terminal from its device number, as `ps` would: pseudo-terminals (majors
+/// 136-143) are `pts/N`, virtual consoles (major 4) `ttyN`.
+fn tty_name(tty_nr: u32) -> Option<String> {
+    let major = (tty_nr >> 8) & 0xfff;
+    let minor = (tty_nr & 0xff) | ((tty_nr >> 12) & 0xfff00);
+    match major {
+        0 => None,
+        136..=143 => Some(format!("pts/{}", (major - 136) * 256 + minor)),
+        4 if minor < 64 => Some(format!("tty{minor}")),
+        4 => Some(format!("ttyS{}", minor - 64)),
+        _ => Some(format!("{major}:{minor}")),
+    }
+}
+
+/// The kernel truncates `comm` to 15 bytes; recover the full name from argv[0] when the
+/// two agree on the prefix.
+fn full_comm(pid: u32, comm: &str) -> String {
+    if comm.len() < TASK_COMM_LEN {
+        return comm.to_string();
+    }
+    match std::fs::read(format!("/proc/{pid}/cmdline")) {
+        Ok(cmdline) => comm_from_cmdline(comm, &cmdline),
+        Err(_) => comm.to_string(),
+    }
+}
+
+/// Longest `comm` the kernel keeps, excluding the terminating NUL.
+const TASK_COMM_LEN: usize = 15;
+
+/// Pick the binary name out of a NUL-delimited `cmdline` if `comm` is a truncation of it.
+/// argv[0] is taken whole, since paths may contain spaces.
+fn comm_from_cmdline(comm: &str, cmdline: &[u8]) -> String {
+    let argv0 = cmdline.split(|&b| b == 0).next().unwrap_or_default();
+    let name = binary_name(&String::from_utf8_lossy(argv0));
+    if name.starts_with(comm) {
+        name
+    } else {
+        comm.to_string()
+    }
+}
+
+/// Shells out to `ps` and `lsof`, for systems without `/proc` (macOS).
+pub struct Ps;
+
+impl ProcessSource for Ps {
+    fn processes(&self) -> Option<Vec<ProcessInfo>> {
+        let output = Command::new("ps")
+            .args(["-eo", "pid=,ppid=,tty=,comm="])
+            .output()
+            .ok()?;
+
+        if !output.status.success() {
+            return None;
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Some(stdout.lines().filter_map(parse_ps_line).collect())
+    }
+
+    fn process(&self, pid: u32) -> Option<ProcessInfo> {
+        let output = Command::new("ps")
+            .args(["-o", "pid=,ppid=,tty=,comm=", "-p", &pid.to_string()])
+            .output()
+            .ok()?;
+
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find_map(parse_ps_line)
+    }
+
+    fn cwd(&self, pid: u32) -> Option<String> {
+        // -Fn prints fields one per line; the cwd path is the line starting with `n`
+        let output = Command::new("lsof")
+            .args(["-a", "-p", &pid.to_string(), "-d", "cwd", "-Fn"])
+            .output()
+            .ok()?;
+
+        if !output.status.success() {
+            return None;
+        }
+
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find_map(|l| l.strip_prefix('n'))
+            .map(str::to_string)
+    }
+
+    /// One `ps -eo` call instead of a `ps` per hop, since hooks walk up on every event.
+    fn ancestry(&self, pid: u32, max_hops: usize) -> Vec<ProcessInfo> {
+        let Some(table) = self.processes() else {
+            return Vec::new();
+        };
+        let mut by_pid: HashMap<u32, ProcessInfo> = table.into_iter().map(|p| (p.pid, p)).collect();
+        walk_ancestry(pid, max_hops, |pid| by_pid.remove(&pid))
+    }
+
+    fn env_vars(&self, pid: u32, names: &[&str]) -> Option<Vec<String>> {
+        // `ps e` appends the environment to the command as NAME=value words; values with
+        // spaces can't be told apart, which is fine for the ids this is used for
+        let output = Command::new("ps")
+            .args(["eww", "-o", "command=", "-p", &pid.to_string()])
+            .output()
+            .ok()?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        names
+            .iter()
+            .map(|name| {
+                stdout.split_whitespace().find_map(|word| {
+                    let value = word.strip_prefix(name)?.strip_prefix('=')?;
+                    Some(value.to_string())
+                })
+            })
+            .collect()
+    }
+}
+
+/// Parse a `pid ppid tty comm` line; `ps` shows `?` (Linux) or `??` (macOS) for no tty.
+/// `comm` is the rest of the line, since macOS prints the full path, spaces included.
+fn parse_ps_line(line: &str) -> Option<ProcessInfo> {
+    let mut rest = line.trim();
+    let mut field = || {
+        let (field, tail) = rest.split_once(char::is_whitespace)?;
+        rest = tail.trim_start();
+        Some(field)
+    };
+    let pid: u32 = field()?.parse().ok()?;
+    let ppid: u32 = field()?.parse().ok()?;
+    let tty = field()?;
+    if rest.is_empty() {
+        return None;
+    }
+    Some(ProcessInfo {
+        pid,
+        ppid,
+        comm: binary_name(rest),
+        tty: (!tty.starts_with('?')).then(|| tty.to_string()),
+    })
+}
+
+/// The nearest ancestor of `pid` (itself excluded) named `name`, looking at most
+/// `max_hops` levels up.
+pub fn find_ancestor(
+    source: &dyn ProcessSource,
+    pid: u32,
+    name: &str,
+    max_hops: usize,
+) -> Option<ProcessInfo> {
+    source
+        .ancestry(pid, max_hops)
+        .into_iter()
+        .skip(1)
+        .find(|p| p.comm == name)
+}
+
+/// Extract just the binary name from a path; login shells show as `-zsh`.
+fn binary_name(path: &str) -> String {
+    path.rsplit('/')
+        .next()
+        .unwrap_or(path)
+        .trim_start_matches('-')
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proc_stat_comm_with_parens_and_spaces() {
+        let stat = "4242 (my (odd) name) S 17 4242 4242 34816 4242 4194560 0 0";
+        assert_eq!(parse_proc_stat(stat), Some(("my (odd) name", 17, 34816)));
+    }
+
+    #[test]
+    fn proc_stat_of_this_process() {
+        let me = read_proc_stat(std::process::id()).expect("own /proc/<pid>/stat");
+        assert_eq!(me.pid, std::process::id());
+        assert_eq!(me.ppid, std::os::unix::process::parent_id());
+    }
+
+    #[test]
+    fn truncated_comm_recovered_from_argv0() {
+        let comm = "claude-session-";
+        assert_eq!(comm.len(), TASK_COMM_LEN);
+        let cmdline = b"/opt/my tools/claude-session-worker\0--resume\0abc\0";
+        assert_eq!(comm_from_cmdline(comm, cmdline), "claude-session-worker");
+    }
+
+    #[test]
+    fn argv0_that_disagrees_keeps_comm() {
+        let comm = "claude-session-";
+        assert_eq!(comm_from_cmdline(comm, b"node\0/usr/lib/cli.js\0"), comm);
+        assert_eq!(full_comm(std::process::id(), "short"), "short");
+    }
+
+    #[test]
+    fn tty_device_numbers() {
+        // tty_nr packs minor bits 0-7 in bits 0-7, the major in bits 8-19 and the
+        // remaining minor bits in bits 20-31
+        let dev = |major: u32, minor: u32| (minor & 0xff) | (major << 8) | ((minor & !0xff) << 12);
+        assert_eq!(tty_name(0), None);
+        assert_eq!(tty_name(dev(136, 3)).as_deref(), Some("pts/3"));
+        assert_eq!(tty_name(dev(136, 300)).as_deref(), Some("pts/300"));
+        assert_eq!(tty_name(dev(137, 0)).as_deref(), Some("pts/256"));
+        assert_eq!(tty_name(dev(4, 1)).as_deref(), Some("tty1"));
+        assert_eq!(tty_name(dev(4, 65)).as_deref(), Some("ttyS1"));
+    }
+
+    #[test]
+    fn ps_lines() {
+        let p = parse_ps_line("  812     1 pts/3    -zsh").unwrap();
+        assert_eq!((p.pid, p.ppid, p.comm.as_str()), (812, 1, "zsh"));
+        assert_eq!(p.tty.as_deref(), Some("pts/3"));
+
+        let p = parse_ps_line("  90   812 ttys003  /usr/local/bin/claude").unwrap();
+        assert_eq!(p.comm, "claude");
+        assert_eq!(p.tty.as_deref(), Some("ttys003"));
+
+        let p = parse_ps_line("1207     1 ??       /Applications/My App.app/Contents/MacOS/My App")
+            .unwrap();
+        assert_eq!((p.comm.as_str(), p.tty), ("My App", None));
+
+        assert!(parse_ps_line("5 1 ? kworker/0:1").unwrap().tty.is_none());
+        assert!(parse_ps_line("5 1 ?").is_none());
+        assert!(parse_ps_line("PID PPID TT COMM").is_none());
+    }
+}
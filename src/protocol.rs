@@ -1,18 +1,203 @@
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-use crate::session::Session;
+use crate::mux::MuxKind;
+use crate::session::{HistoryEvent, Session, Status};
 
-pub const SOCKET_PATH: &str = "/tmp/bottycall.sock";
+/// Default per-user socket location: `$XDG_RUNTIME_DIR/bottycall.sock`, or
+/// `/tmp/bottycall-<uid>/bottycall.sock` where no runtime dir exists (e.g. macOS
+/// launchd). The daemon creates that directory with mode 0700.
+pub fn default_socket_path() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR").filter(|v| !v.is_empty()) {
+        Some(dir) => PathBuf::from(dir).join("bottycall.sock"),
+        None => PathBuf::from(format!("/tmp/bottycall-{}", current_uid())).join("bottycall.sock"),
+    }
+}
 
-/// First line sent by a connecting client to identify itself.
-#[derive(Debug, Deserialize)]
+/// The real uid of this process.
+pub fn current_uid() -> u32 {
+    // SAFETY: getuid takes no arguments, cannot fail and touches no memory we own.
+    unsafe { libc::getuid() }
+}
+
+/// Refuse a socket owned by another user. Whoever owns the socket receives every hook
+/// payload and prompt, and anyone can create files in a shared directory like /tmp.
+pub fn check_socket_owner(socket: &Path) -> anyhow::Result<()> {
+    let owner = std::fs::metadata(socket)
+        .map_err(|e| anyhow::anyhow!("cannot reach daemon at {}: {e}", socket.display()))?
+        .uid();
+    anyhow::ensure!(
+        owner == current_uid(),
+        "refusing daemon socket {} owned by uid {owner}, not by you",
+        socket.display()
+    );
+    Ok(())
+}
+
+/// Protocol version spoken by this build. Bump on incompatible message changes.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Requests this build's daemon understands, advertised in the handshake.
+pub const CAPABILITIES: &[&str] = &["report", "subscribe", "ping", "history", "command"];
+
+/// First line sent by a connecting client: the request plus the client's protocol
+/// version and capabilities. Clients predating versioning send a bare `ClientHello`,
+/// which parses here with `version: None` and gets no `Welcome`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Hello {
+    #[serde(flatten)]
+    pub request: ClientHello,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
+    /// Requests the client knows how to make and whose replies it can read. A versioned
+    /// client must list the request it is making, or the daemon refuses it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub capabilities: Vec<String>,
+}
+
+impl Hello {
+    pub fn new(request: ClientHello) -> Self {
+        Self {
+            request,
+            version: Some(PROTOCOL_VERSION),
+            capabilities: CAPABILITIES.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+
+    /// Check a versioned hello against this daemon: the protocol version must match and
+    /// the request's capability must be declared. Unversioned hellos always pass.
+    pub fn check(&self) -> Result<(), String> {
+        let Some(version) = self.version else {
+            return Ok(());
+        };
+        if version != PROTOCOL_VERSION {
+            return Err(format!(
+                "client speaks protocol v{version} but daemon speaks v{PROTOCOL_VERSION}"
+            ));
+        }
+        let needed = self.required_capability();
+        if !self.capabilities.iter().any(|c| c == needed) {
+            return Err(format!("client did not declare the {needed:?} capability"));
+        }
+        Ok(())
+    }
+
+    /// The capability a versioned client must declare to make this request.
+    pub fn required_capability(&self) -> &'static str {
+        match self.request {
+            ClientHello::Report(_) => "report",
+            ClientHello::Subscribe => "subscribe",
+            ClientHello::Ping => "ping",
+            ClientHello::History { .. } => "history",
+            ClientHello::Command(_) => "command",
+        }
+    }
+
+    /// Serialize as one newline-terminated protocol line.
+    pub fn to_line(&self) -> serde_json::Result<String> {
+        let mut line = serde_json::to_string(self)?;
+        line.push('\n');
+        Ok(line)
+    }
+}
+
+/// Request part of the first line sent by a connecting client.
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum ClientHello {
     #[serde(rename = "report")]
     Report(HookReport),
     #[serde(rename = "subscribe")]
     Subscribe,
+    /// Liveness probe; the daemon answers with `ServerMessage::Pong`.
+    #[serde(rename = "ping")]
+    Ping,
+    /// Request recent hook events for one session; answered with `ServerMessage::History`.
+    #[serde(rename = "history")]
+    History { session_id: String },
+    /// Control request; answered with one typed `ServerMessage` or `Error`.
+    #[serde(rename = "command")]
+    Command(ControlCommand),
+}
+
+/// Operations on the `command` channel. Sessions may be named by id, unique id prefix or slug.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum ControlCommand {
+    /// Answered with `Pong`.
+    Ping,
+    /// Answered with `Session`.
+    GetSession { session: String },
+    /// Drop a session from the daemon; answered with `Forgotten`.
+    ForgetSession { session: String },
+    /// Override a session's status; answered with the updated `Session`.
+    SetStatus { session: String, status: Status },
+    /// Set a session's display name, or clear it with `None`. With `dir`, the name is
+    /// stored for the session's working directory and inherited by every session there.
+    /// Answered with the updated `Session`.
+    RenameSession {
+        session: String,
+        name: Option<String>,
+        #[serde(default)]
+        dir: bool,
+    },
+    /// Replace a session's labels (an empty list clears them); `dir` as for `RenameSession`.
+    /// Answered with the updated `Session`.
+    SetLabels {
+        session: String,
+        labels: Vec<String>,
+        #[serde(default)]
+        dir: bool,
+    },
+    /// Answer the permission dialog showing in a session's pane by typing the matching
+    /// key. Refused unless the pane shows such a dialog. Answered with the `Session`.
+    AnswerPrompt {
+        session: String,
+        answer: PromptAnswer,
+    },
+    /// Type a prompt into a session's pane and submit it; multi-line text is pasted in
+    /// one piece. Refused while a permission dialog is showing. Answered with the `Session`.
+    SendPrompt { session: String, text: String },
+    /// Stop the daemon; answered with `ShuttingDown`.
+    Shutdown,
+}
+
+/// An answer to a Claude Code permission dialog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PromptAnswer {
+    /// "Yes": allow this once.
+    Approve,
+    /// "Yes, and don't ask again": allow and remember.
+    ApproveAlways,
+    /// "No": refuse and let the user tell Claude what to do instead.
+    Deny,
+}
+
+impl PromptAnswer {
+    pub fn label(self) -> &'static str {
+        match self {
+            PromptAnswer::Approve => "Approve once",
+            PromptAnswer::ApproveAlways => "Approve always",
+            PromptAnswer::Deny => "Deny",
+        }
+    }
+}
+
+impl std::str::FromStr for PromptAnswer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "approve" | "yes" | "y" => Ok(PromptAnswer::Approve),
+            "always" | "approve-always" | "approve_always" => Ok(PromptAnswer::ApproveAlways),
+            "deny" | "no" | "n" => Ok(PromptAnswer::Deny),
+            _ => Err(format!("unknown answer {s:?} (expected approve, always or deny)")),
+        }
+    }
 }
 
 /// Sent by the hook reporter to the daemon.
@@ -28,20 +213,84 @@ pub struct HookReport {
     pub stop_hook_active: Option<bool>,
     #[serde(default)]
     pub tool_name: Option<String>,
+    /// Pane id in `mux`.
     #[serde(default)]
     pub tmux_pane: Option<String>,
+    /// Multiplexer the hook ran in; reporters predating screen and Zellij support only
+    /// report tmux panes.
+    #[serde(default)]
+    pub mux: Option<MuxKind>,
+    /// Process id of the claude process that ran the hook.
+    #[serde(default)]
+    pub claude_pid: Option<u32>,
+    /// Controlling terminal of that process.
+    #[serde(default)]
+    pub tty: Option<String>,
     #[serde(default)]
     pub ts: Option<DateTime<Utc>>,
+    /// Why a session started: "startup", "resume", "clear" or "compact".
+    #[serde(default)]
+    pub source: Option<String>,
+    /// What triggered a compaction: "manual" or "auto".
+    #[serde(default)]
+    pub trigger: Option<String>,
 }
 
-/// Messages sent from the daemon to TUI subscribers.
+/// Messages sent from the daemon to clients.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum ServerMessage {
     #[serde(rename = "snapshot")]
     Snapshot { sessions: Vec<Session> },
     #[serde(rename = "update")]
-    Update { session: Session },
+    Update { session: Box<Session> },
     #[serde(rename = "remove")]
     Remove { session_id: String },
+    #[serde(rename = "pong")]
+    Pong { pid: u32 },
+    #[serde(rename = "history")]
+    History {
+        session_id: String,
+        events: Vec<HistoryEvent>,
+    },
+    #[serde(rename = "session")]
+    Session { session: Box<Session> },
+    #[serde(rename = "forgotten")]
+    Forgotten { session_id: String },
+    #[serde(rename = "shutting_down")]
+    ShuttingDown,
+    /// First reply to a versioned `Hello`.
+    #[serde(rename = "welcome")]
+    Welcome {
+        version: u32,
+        capabilities: Vec<String>,
+    },
+    /// The request was rejected; the daemon closes the connection after sending this.
+    #[serde(rename = "error")]
+    Error { message: String },
+}
+
+impl ServerMessage {
+    pub fn welcome() -> Self {
+        ServerMessage::Welcome {
+            version: PROTOCOL_VERSION,
+            capabilities: CAPABILITIES.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+}
+
+/// Check the daemon's first reply to a versioned `Hello`.
+///
+/// Returns `Ok(None)` for a compatible `Welcome`. A daemon predating versioning sends no
+/// `Welcome`, so any other message is handed back for the caller to process normally.
+pub fn check_handshake(first: ServerMessage) -> anyhow::Result<Option<ServerMessage>> {
+    match first {
+        ServerMessage::Welcome { version, .. } if version == PROTOCOL_VERSION => Ok(None),
+        ServerMessage::Welcome { version, .. } => anyhow::bail!(
+            "daemon speaks protocol v{version} but this client speaks v{PROTOCOL_VERSION}; \
+             restart the daemon with a matching bottycall binary"
+        ),
+        ServerMessage::Error { message } => anyhow::bail!("daemon refused request: {message}"),
+        other => Ok(Some(other)),
+    }
 }
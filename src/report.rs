@@ -1,13 +1,20 @@
 use std::io::{Read, Write};
 use std::os::unix::net::UnixStream;
+use std::path::Path;
 use std::time::Duration;
 
-use crate::protocol::SOCKET_PATH;
+use crate::mux::{self, MuxKind};
+use crate::procs::{self, ProcessInfo};
+use crate::protocol::{PROTOCOL_VERSION, check_socket_owner};
+
+/// Parent levels searched for the claude process: hooks run through a shell, which may
+/// itself be wrapped (e.g. by `env` or a version manager shim).
+const MAX_ANCESTOR_HOPS: usize = 4;
 
 /// Hook reporter entry point. Reads stdin, extracts fields, sends to daemon.
 /// All errors are silently ignored — must never block Claude Code.
-pub fn run(event: &str) {
-    let _ = run_inner(event);
+pub fn run(event: &str, socket: &Path, process_name: &str) {
+    let _ = run_inner(event, socket, process_name);
 }
 
 fn debug_log(event: &str, msg: &str) {
@@ -21,7 +28,7 @@ fn debug_log(event: &str, msg: &str) {
     }
 }
 
-fn run_inner(event: &str) -> Option<()> {
+fn run_inner(event: &str, socket: &Path, process_name: &str) -> Option<()> {
     // Read stdin (the hook payload from Claude Code)
     let mut input = String::new();
     std::io::stdin().read_to_string(&mut input).ok()?;
@@ -31,24 +38,59 @@ fn run_inner(event: &str) -> Option<()> {
     let hook: serde_json::Value = serde_json::from_str(&input).ok()?;
 
     // Skip the parent "startup" session — only the "resume" session gets real events
-    if event == "SessionStart" {
-        if hook.get("source").and_then(|v| v.as_str()) == Some("startup") {
-            return Some(());
-        }
+    if event == "SessionStart" && hook.get("source").and_then(|v| v.as_str()) == Some("startup")
+    {
+        return Some(());
     }
 
+    // Get the multiplexer pane from environment
+    let pane = mux::pane_from_env();
+
+    // The claude process running this hook identifies sessions outside tmux
+    let claude = procs::find_ancestor(
+        &*procs::detect(),
+        std::process::id(),
+        process_name,
+        MAX_ANCESTOR_HOPS,
+    );
+
+    let report = build_report(event, &hook, pane, claude)?;
+    let mut payload = serde_json::to_string(&report).ok()?;
+    payload.push('\n');
+
+    // Connect to daemon and send
+    check_socket_owner(socket).ok()?;
+    let mut stream = UnixStream::connect(socket).ok()?;
+    stream
+        .set_write_timeout(Some(Duration::from_millis(100)))
+        .ok()?;
+    stream.write_all(payload.as_bytes()).ok()?;
+    stream.flush().ok()?;
+
+    Some(())
+}
+
+/// Build the compact report line for one hook payload, as a versioned `Hello` the daemon
+/// accepts without a reply.
+fn build_report(
+    event: &str,
+    hook: &serde_json::Value,
+    pane: Option<(MuxKind, String)>,
+    claude: Option<ProcessInfo>,
+) -> Option<serde_json::Value> {
     let session_id = hook.get("session_id")?.as_str()?;
     let cwd = hook.get("cwd").and_then(|v| v.as_str());
     let message = hook.get("message").and_then(|v| v.as_str());
     let stop_hook_active = hook.get("stop_hook_active").and_then(|v| v.as_bool());
     let tool_name = hook.get("tool_name").and_then(|v| v.as_str());
-
-    // Get tmux pane from environment
-    let tmux_pane = std::env::var("TMUX_PANE").ok();
+    let source = hook.get("source").and_then(|v| v.as_str());
+    let trigger = hook.get("trigger").and_then(|v| v.as_str());
 
     // Build compact report JSON
     let mut report = serde_json::json!({
         "type": "report",
+        "version": PROTOCOL_VERSION,
+        "capabilities": ["report"],
         "session_id": session_id,
         "hook_event_name": event,
     });
@@ -65,21 +107,66 @@ fn run_inner(event: &str) -> Option<()> {
     if let Some(v) = tool_name {
         obj.insert("tool_name".into(), v.into());
     }
-    if let Some(v) = &tmux_pane {
-        obj.insert("tmux_pane".into(), v.clone().into());
+    if let Some(v) = source {
+        obj.insert("source".into(), v.into());
+    }
+    if let Some(v) = trigger {
+        obj.insert("trigger".into(), v.into());
+    }
+    if let Some((kind, pane_id)) = pane {
+        obj.insert("tmux_pane".into(), pane_id.into());
+        obj.insert("mux".into(), kind.label().into());
+    }
+    if let Some(claude) = claude {
+        obj.insert("claude_pid".into(), claude.pid.into());
+        if let Some(tty) = claude.tty {
+            obj.insert("tty".into(), tty.into());
+        }
     }
     obj.insert("ts".into(), chrono::Utc::now().to_rfc3339().into());
 
-    let mut payload = serde_json::to_string(&report).ok()?;
-    payload.push('\n');
+    Some(report)
+}
 
-    // Connect to daemon and send
-    let mut stream = UnixStream::connect(SOCKET_PATH).ok()?;
-    stream
-        .set_write_timeout(Some(Duration::from_millis(100)))
-        .ok()?;
-    stream.write_all(payload.as_bytes()).ok()?;
-    stream.flush().ok()?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{ClientHello, Hello};
 
-    Some(())
+    #[test]
+    fn report_passes_the_daemon_handshake() {
+        let hook = serde_json::json!({
+            "session_id": "abc123",
+            "cwd": "/home/me/repo",
+            "tool_name": "Bash",
+        });
+        let claude = ProcessInfo {
+            pid: 4242,
+            ppid: 1,
+            comm: "claude".to_string(),
+            tty: Some("pts/3".to_string()),
+        };
+        let pane = Some((MuxKind::Screen, "123.work:2".to_string()));
+        let report = build_report("PreToolUse", &hook, pane, Some(claude)).unwrap();
+        let line = serde_json::to_string(&report).unwrap();
+
+        let hello: Hello = serde_json::from_str(&line).unwrap();
+        assert_eq!(hello.check(), Ok(()));
+        let ClientHello::Report(report) = hello.request else {
+            panic!("not a report: {line}");
+        };
+        assert_eq!(report.session_id, "abc123");
+        assert_eq!(report.hook_event_name, "PreToolUse");
+        assert_eq!(report.tmux_pane.as_deref(), Some("123.work:2"));
+        assert_eq!(report.mux, Some(MuxKind::Screen));
+        assert_eq!(report.claude_pid, Some(4242));
+        assert_eq!(report.tty.as_deref(), Some("pts/3"));
+    }
+
+    #[test]
+    fn versioned_hello_without_capability_is_refused() {
+        let line = r#"{"type":"report","version":1,"session_id":"a","hook_event_name":"Stop"}"#;
+        let hello: Hello = serde_json::from_str(line).unwrap();
+        assert!(hello.check().is_err());
+    }
 }
@@ -0,0 +1,30 @@
+use std::io::Read;
+use std::path::Path;
+
+use crate::ctl::request;
+use crate::protocol::{ControlCommand, ServerMessage};
+
+/// Type a prompt into a session's pane and submit it.
+pub fn run(socket: &Path, session: String, text: Option<String>) {
+    if let Err(e) = run_inner(socket, session, text) {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
+}
+
+fn run_inner(socket: &Path, session: String, text: Option<String>) -> anyhow::Result<()> {
+    let text = match text {
+        Some(text) => text,
+        None => {
+            let mut text = String::new();
+            std::io::stdin().read_to_string(&mut text)?;
+            text
+        }
+    };
+
+    match request(socket, ControlCommand::SendPrompt { session, text })? {
+        ServerMessage::Session { session } => println!("sent to {}", session.slug),
+        other => anyhow::bail!("unexpected reply: {other:?}"),
+    }
+    Ok(())
+}
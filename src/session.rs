@@ -1,9 +1,13 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::Path;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+use crate::mux::MuxKind;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Status {
+    #[default]
     Idle,
     Working,
     Attention,
@@ -25,9 +29,62 @@ impl Status {
             Status::Attention => "Attention",
         }
     }
+
+    /// Sort rank: Attention first, then Working, then Idle.
+    pub fn priority(&self) -> u8 {
+        match self {
+            Status::Attention => 0,
+            Status::Working => 1,
+            Status::Idle => 2,
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl std::str::FromStr for Status {
+    type Err = String;
+
+    /// Parse a status label case-insensitively ("attention", "Working", ...).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [Status::Idle, Status::Working, Status::Attention]
+            .into_iter()
+            .find(|st| st.label().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown status {s:?} (expected idle, working or attention)"))
+    }
+}
+
+/// Derived from timers rather than hooks: a session that looks stuck or abandoned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SessionFlag {
+    /// Working, but no hook event or pane change for a while.
+    Stalled,
+    /// Idle for a long time.
+    Forgotten,
+}
+
+impl SessionFlag {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SessionFlag::Stalled => "stalled",
+            SessionFlag::Forgotten => "forgotten",
+        }
+    }
+}
+
+/// How the daemon derives session slugs (`slug_style` in the `[daemon]` config).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SlugStyle {
+    /// The shortest unique form of `repo/subdir`, `repo/subdir@branch`,
+    /// `repo:worktree/subdir@branch`.
+    #[default]
+    Smart,
+    /// Always include the branch: `repo/subdir@branch`.
+    RepoBranch,
+    /// The last component of the working directory.
+    Directory,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Session {
     pub session_id: String,
     pub slug: String,
@@ -35,17 +92,92 @@ pub struct Session {
     pub last_activity: DateTime<Utc>,
     #[serde(default)]
     pub cwd: Option<String>,
+    /// Pane id in `mux`; the name predates support for multiplexers other than tmux.
     #[serde(default)]
     pub tmux_pane: Option<String>,
+    /// Multiplexer the pane belongs to; `None` (tmux) for sessions recorded before
+    /// other multiplexers were supported.
+    #[serde(default)]
+    pub mux: Option<MuxKind>,
+    /// Process id of the claude process, when known.
+    #[serde(default)]
+    pub pid: Option<u32>,
+    /// Controlling terminal of the claude process, e.g. `pts/3`.
+    #[serde(default)]
+    pub tty: Option<String>,
     #[serde(default)]
     pub git_repo: Option<String>,
     #[serde(default)]
     pub git_branch: Option<String>,
+    /// Top level of the checkout; differs from `git_repo` in a linked worktree.
+    #[serde(default)]
+    pub git_worktree: Option<String>,
     /// Token count from live tmux pane capture of Claude Code's status bar.
     #[serde(default)]
     pub input_tokens: u64,
     #[serde(default)]
     pub output_tokens: u64,
+    /// When the session entered its current status.
+    #[serde(default)]
+    pub status_since: Option<DateTime<Utc>>,
+    /// Tool named by the most recent tool hook.
+    #[serde(default)]
+    pub last_tool: Option<String>,
+    /// Message from the most recent Notification hook.
+    #[serde(default)]
+    pub last_message: Option<String>,
+    /// Tool currently running (between PreToolUse and PostToolUse/Stop).
+    #[serde(default)]
+    pub current_tool: Option<String>,
+    #[serde(default)]
+    pub tool_started: Option<DateTime<Utc>>,
+    /// Subagents launched through the Task tool that haven't reported SubagentStop yet.
+    #[serde(default)]
+    pub subagents: u32,
+    /// What triggered the compaction in progress ("auto" or "manual"), between
+    /// PreCompact and the next hook event.
+    #[serde(default)]
+    pub compacting: Option<String>,
+    /// When the context was last compacted.
+    #[serde(default)]
+    pub last_compaction: Option<DateTime<Utc>>,
+    /// Model shown in the pane's status bar.
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Context left until auto-compaction, in percent, as shown in the pane.
+    #[serde(default)]
+    pub context_left: Option<u8>,
+    /// Session cost in USD as shown in the pane's status bar.
+    #[serde(default)]
+    pub cost_usd: Option<f64>,
+    /// Set by the daemon's timers; cleared by the next sign of activity.
+    #[serde(default)]
+    pub flag: Option<SessionFlag>,
+    /// Name chosen by the user; when set it is also the session's slug.
+    #[serde(default)]
+    pub display_name: Option<String>,
+    /// Free-form labels chosen by the user.
+    #[serde(default)]
+    pub labels: Vec<String>,
+}
+
+impl Session {
+    /// The multiplexer and pane the session runs in.
+    pub fn pane(&self) -> Option<(MuxKind, &str)> {
+        let pane = self.tmux_pane.as_deref()?;
+        Some((self.mux.unwrap_or_default(), pane))
+    }
+}
+
+/// One hook event recorded in a session's history ring.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEvent {
+    pub event: String,
+    #[serde(default)]
+    pub tool_name: Option<String>,
+    #[serde(default)]
+    pub message: Option<String>,
+    pub ts: DateTime<Utc>,
 }
 
 /// Resolve the git repository root for a working directory.
@@ -75,6 +207,22 @@ pub fn git_repo_from_cwd(cwd: &str) -> Option<String> {
         .map(|s| s.to_string())
 }
 
+/// Resolve the top level of the checkout (the worktree root) for a working directory.
+pub fn git_worktree_from_cwd(cwd: &str) -> Option<String> {
+    let output = std::process::Command::new("git")
+        .args(["-C", cwd, "rev-parse", "--show-toplevel"])
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let toplevel = String::from_utf8(output.stdout).ok()?;
+    let toplevel = toplevel.trim();
+    (!toplevel.is_empty()).then(|| toplevel.to_string())
+}
+
 /// Resolve the current git branch for a working directory.
 pub fn git_branch_from_cwd(cwd: &str) -> Option<String> {
     let output = std::process::Command::new("git")
@@ -103,26 +251,150 @@ pub fn slug_from_cwd(cwd: &str) -> String {
         .to_string()
 }
 
+/// Candidate slugs for a session under `style`, shortest first.
+fn slug_candidates(session: &Session, style: SlugStyle) -> Vec<String> {
+    if let Some(name) = &session.display_name {
+        return vec![name.clone()];
+    }
+    let Some(cwd) = session.cwd.as_deref().filter(|c| !c.is_empty()) else {
+        return vec![session.session_id.chars().take(8).collect()];
+    };
+    let dir = slug_from_cwd(cwd);
+    if style == SlugStyle::Directory {
+        return vec![dir];
+    }
+    let Some(repo) = session.git_repo.as_deref() else {
+        // Outside git, fall back to including the parent directory
+        let path = Path::new(cwd);
+        let parent = path.parent().and_then(|p| p.file_name()).and_then(|n| n.to_str());
+        return match parent {
+            Some(parent) => vec![dir.clone(), format!("{parent}/{dir}")],
+            None => vec![dir],
+        };
+    };
+
+    let repo_name = slug_from_cwd(repo);
+    let root = session.git_worktree.as_deref().unwrap_or(repo);
+    let subpath = Path::new(cwd)
+        .strip_prefix(root)
+        .ok()
+        .and_then(|p| p.to_str())
+        .filter(|p| !p.is_empty())
+        .map(|p| format!("/{p}"))
+        .unwrap_or_default();
+    let worktree = session
+        .git_worktree
+        .as_deref()
+        .filter(|wt| *wt != repo)
+        .map(slug_from_cwd);
+    let branch = session
+        .git_branch
+        .as_deref()
+        .map(|b| format!("@{b}"))
+        .unwrap_or_default();
+
+    let mut forms = Vec::new();
+    if style == SlugStyle::Smart || branch.is_empty() {
+        forms.push(format!("{repo_name}{subpath}"));
+    }
+    if !branch.is_empty() {
+        forms.push(format!("{repo_name}{subpath}{branch}"));
+    }
+    if let Some(worktree) = worktree {
+        forms.push(format!("{repo_name}:{worktree}{subpath}{branch}"));
+    }
+    forms
+}
+
+/// Give every session the shortest candidate slug that no other session shares.
+/// Sessions that still collide after their longest form are left for
+/// `disambiguate_slugs`. Returns the ids of sessions whose slug changed.
+pub fn assign_slugs<'a>(
+    sessions: impl IntoIterator<Item = &'a mut Session>,
+    style: SlugStyle,
+) -> Vec<String> {
+    let mut sessions: Vec<&mut Session> = sessions.into_iter().collect();
+    let candidates: Vec<Vec<String>> = sessions
+        .iter()
+        .map(|s| slug_candidates(s, style))
+        .collect();
+    let mut level = vec![0; sessions.len()];
+
+    loop {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for (forms, &l) in candidates.iter().zip(&level) {
+            *counts.entry(forms[l].as_str()).or_default() += 1;
+        }
+        let mut bumped = false;
+        for (forms, l) in candidates.iter().zip(level.iter_mut()) {
+            if counts[forms[*l].as_str()] > 1 && *l + 1 < forms.len() {
+                *l += 1;
+                bumped = true;
+            }
+        }
+        if !bumped {
+            break;
+        }
+    }
+
+    let mut changed = Vec::new();
+    for ((session, forms), l) in sessions.iter_mut().zip(&candidates).zip(level) {
+        if session.slug != forms[l] {
+            session.slug = forms[l].clone();
+            changed.push(session.session_id.clone());
+        }
+    }
+    changed
+}
+
+/// The " [abcd]" suffix `disambiguate_slugs` appends for this session.
+fn slug_suffix(session: &Session) -> String {
+    let prefix: String = session.session_id.chars().take(4).collect();
+    format!(" [{prefix}]")
+}
+
+/// The session's slug without the disambiguation suffix added for it, if any.
+/// Brackets that are part of the name itself (e.g. "fix [urgent]") are kept.
+pub fn base_slug(session: &Session) -> &str {
+    session
+        .slug
+        .strip_suffix(slug_suffix(session).as_str())
+        .unwrap_or(&session.slug)
+}
+
 /// Disambiguate duplicate slugs by appending a short session_id prefix.
 pub fn disambiguate_slugs(sessions: &mut [Session]) {
-    // Count slug occurrences
+    // Compare without any suffix added by an earlier pass, since clients re-sort
+    // sessions that were already disambiguated
     let mut counts: HashMap<String, usize> = HashMap::new();
     for s in sessions.iter() {
-        *counts.entry(s.slug.clone()).or_default() += 1;
+        *counts.entry(base_slug(s).to_string()).or_default() += 1;
     }
 
     // For any duplicated slug, append [xxxx] from session_id
     for s in sessions.iter_mut() {
-        if counts.get(&s.slug).copied().unwrap_or(0) > 1 {
-            let prefix: String = s.session_id.chars().take(4).collect();
-            // Strip any existing disambiguation suffix first
-            if let Some(base) = s.slug.split(" [").next() {
-                s.slug = format!("{base} [{prefix}]");
-            }
+        let base = base_slug(s).to_string();
+        if counts[&base] > 1 {
+            s.slug = format!("{base}{}", slug_suffix(s));
+        } else if base.len() != s.slug.len() {
+            // No longer a duplicate: drop the suffix from an earlier pass
+            s.slug = base;
         }
     }
 }
 
+/// Sort sessions for display (Attention first, then Working, then Idle, each by slug)
+/// and disambiguate duplicate slugs.
+pub fn sort_sessions(sessions: &mut [Session]) {
+    sessions.sort_by(|a, b| {
+        a.status
+            .priority()
+            .cmp(&b.status.priority())
+            .then(a.slug.cmp(&b.slug))
+    });
+    disambiguate_slugs(sessions);
+}
+
 /// Format a token count as a compact human-readable string (e.g. "12k", "1.4M").
 pub fn format_tokens(n: u64) -> String {
     if n == 0 {
@@ -140,6 +412,18 @@ pub fn format_tokens(n: u64) -> String {
     format!("{:.1}M", n as f64 / 1_000_000.0)
 }
 
+/// Format an elapsed duration compactly (e.g. "42s", "2m14s", "1h05m").
+pub fn format_elapsed(from: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let secs = (now - from).num_seconds().max(0);
+    if secs < 60 {
+        format!("{secs}s")
+    } else if secs < 3600 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h{:02}m", secs / 3600, (secs % 3600) / 60)
+    }
+}
+
 /// Format a duration as a human-readable relative time string.
 pub fn relative_time(from: DateTime<Utc>, now: DateTime<Utc>) -> String {
     let secs = (now - from).num_seconds().max(0);
@@ -155,3 +439,159 @@ pub fn relative_time(from: DateTime<Utc>, now: DateTime<Utc>) -> String {
         format!("{}d", secs / 86400)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(id: &str, cwd: &str, repo: Option<&str>, worktree: Option<&str>) -> Session {
+        Session {
+            session_id: id.to_string(),
+            cwd: Some(cwd.to_string()),
+            git_repo: repo.map(str::to_string),
+            git_worktree: worktree.or(repo).map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    fn on_branch(mut session: Session, branch: &str) -> Session {
+        session.git_branch = Some(branch.to_string());
+        session
+    }
+
+    fn slugs(sessions: &mut [Session], style: SlugStyle) -> Vec<String> {
+        assign_slugs(sessions.iter_mut(), style);
+        disambiguate_slugs(sessions);
+        sessions.iter().map(|s| s.slug.clone()).collect()
+    }
+
+    #[test]
+    fn lone_session_gets_the_shortest_form() {
+        let mut sessions = [on_branch(
+            session("a", "/src/app", Some("/src/app"), None),
+            "main",
+        )];
+        assert_eq!(slugs(&mut sessions, SlugStyle::Smart), ["app"]);
+    }
+
+    #[test]
+    fn same_repo_on_two_branches() {
+        let mut sessions = [
+            on_branch(session("a", "/src/app", Some("/src/app"), None), "main"),
+            on_branch(
+                session(
+                    "b",
+                    "/src/app-feat",
+                    Some("/src/app"),
+                    Some("/src/app-feat"),
+                ),
+                "feat",
+            ),
+        ];
+        assert_eq!(
+            slugs(&mut sessions, SlugStyle::Smart),
+            ["app@main", "app@feat"]
+        );
+    }
+
+    #[test]
+    fn worktree_without_branch() {
+        // Detached HEADs leave only the worktree name to tell the checkouts apart
+        let mut sessions = [
+            session("a", "/src/app", Some("/src/app"), None),
+            session(
+                "b",
+                "/src/wt/review",
+                Some("/src/app"),
+                Some("/src/wt/review"),
+            ),
+        ];
+        assert_eq!(
+            slugs(&mut sessions, SlugStyle::Smart),
+            ["app", "app:review"]
+        );
+    }
+
+    #[test]
+    fn nested_subdirectories() {
+        let mut sessions = [
+            on_branch(session("a", "/src/app", Some("/src/app"), None), "main"),
+            on_branch(
+                session("b", "/src/app/sub/deeper", Some("/src/app"), None),
+                "main",
+            ),
+        ];
+        assert_eq!(
+            slugs(&mut sessions, SlugStyle::Smart),
+            ["app", "app/sub/deeper"]
+        );
+    }
+
+    #[test]
+    fn non_git_directories_add_their_parent() {
+        let mut sessions = [session("a", "/home/me/notes", None, None)];
+        assert_eq!(slugs(&mut sessions, SlugStyle::Smart), ["notes"]);
+
+        let mut sessions = [
+            session("a", "/home/me/notes", None, None),
+            session("b", "/work/notes", None, None),
+        ];
+        assert_eq!(
+            slugs(&mut sessions, SlugStyle::Smart),
+            ["me/notes", "work/notes"]
+        );
+    }
+
+    #[test]
+    fn identical_checkouts_fall_back_to_the_id() {
+        let mut sessions = [
+            on_branch(
+                session("aaaa1111", "/src/app", Some("/src/app"), None),
+                "main",
+            ),
+            on_branch(
+                session("bbbb2222", "/src/app", Some("/src/app"), None),
+                "main",
+            ),
+        ];
+        assert_eq!(
+            slugs(&mut sessions, SlugStyle::Smart),
+            ["app@main [aaaa]", "app@main [bbbb]"]
+        );
+        // Re-running on already suffixed slugs is stable
+        disambiguate_slugs(&mut sessions);
+        assert_eq!(sessions[0].slug, "app@main [aaaa]");
+    }
+
+    #[test]
+    fn display_name_wins() {
+        let mut named = on_branch(session("a", "/src/app", Some("/src/app"), None), "main");
+        named.display_name = Some("fix [urgent]".to_string());
+        let mut sessions = [named];
+        assert_eq!(
+            slugs(&mut sessions, SlugStyle::RepoBranch),
+            ["fix [urgent]"]
+        );
+    }
+
+    #[test]
+    fn repo_branch_style_always_shows_the_branch() {
+        let mut sessions = [
+            on_branch(session("a", "/src/app/sub", Some("/src/app"), None), "main"),
+            session("b", "/src/other", Some("/src/other"), None),
+        ];
+        assert_eq!(
+            slugs(&mut sessions, SlugStyle::RepoBranch),
+            ["app/sub@main", "other"]
+        );
+    }
+
+    #[test]
+    fn directory_style_uses_the_last_component() {
+        let mut sessions = [
+            on_branch(session("a", "/src/app/sub", Some("/src/app"), None), "main"),
+            session("b", "/home/me/notes", None, None),
+        ];
+        assert_eq!(slugs(&mut sessions, SlugStyle::Directory), ["sub", "notes"]);
+    }
+}
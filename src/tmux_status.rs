@@ -0,0 +1,152 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::client::fetch_snapshot;
+use crate::session::{Session, Status};
+
+/// Print a one-line status summary for tmux's `status-right`.
+/// Errors print nothing so a stopped daemon simply blanks the segment.
+pub fn run(socket: &Path, format: &str, max_age: Duration) {
+    let Some(sessions) = cached_sessions(socket, max_age) else {
+        return;
+    };
+    println!("{}", render(format, &sessions));
+}
+
+/// Cache file kept next to the socket so each daemon instance has its own.
+fn cache_path(socket: &Path) -> PathBuf {
+    let mut path = socket.as_os_str().to_owned();
+    path.push(".status");
+    PathBuf::from(path)
+}
+
+/// Return the cached snapshot if it is younger than `max_age`, otherwise ask the daemon
+/// and refresh the cache. tmux re-runs status commands every few seconds per client.
+fn cached_sessions(socket: &Path, max_age: Duration) -> Option<Vec<Session>> {
+    let cache = cache_path(socket);
+
+    let fresh = std::fs::metadata(&cache)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| SystemTime::now().duration_since(t).ok())
+        .is_some_and(|age| age < max_age);
+    if fresh
+        && let Some(sessions) = std::fs::read(&cache)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+    {
+        return Some(sessions);
+    }
+
+    let sessions = fetch_snapshot(socket).ok()?;
+    if let Ok(data) = serde_json::to_vec(&sessions) {
+        let tmp = cache.with_extension("status.tmp");
+        if std::fs::write(&tmp, data).is_ok() {
+            let _ = std::fs::rename(&tmp, &cache);
+        }
+    }
+    Some(sessions)
+}
+
+/// Expand a format string.
+///
+/// - `{attention}`, `{working}`, `{idle}`, `{total}` expand to counts.
+/// - `{working?TEXT}` expands to `TEXT` followed by the count, or nothing when it is zero.
+///   TEXT may contain tmux styles, e.g. `{working?#[fg=yellow]⚡}`.
+///
+/// The space separating an empty `{name?TEXT}` segment from its neighbour is dropped;
+/// all other spacing is kept as written.
+fn render(format: &str, sessions: &[Session]) -> String {
+    let count = |status: Status| sessions.iter().filter(|s| s.status == status).count();
+    let lookup = |name: &str| match name {
+        "attention" => Some(count(Status::Attention)),
+        "working" => Some(count(Status::Working)),
+        "idle" => Some(count(Status::Idle)),
+        "total" => Some(sessions.len()),
+        _ => None,
+    };
+
+    let mut out = String::new();
+    let mut rest = format;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find('}') else {
+            // Unclosed brace: emit the remainder literally
+            out.push_str(&rest[start..]);
+            rest = "";
+            break;
+        };
+        let field = &rest[start + 1..start + len];
+        let (name, prefix) = match field.split_once('?') {
+            Some((name, prefix)) => (name, Some(prefix)),
+            None => (field, None),
+        };
+        let mut after = &rest[start + len + 1..];
+        match (lookup(name), prefix) {
+            (Some(0), Some(_)) => {
+                // Drop one of the spaces that surrounded the empty segment
+                if after.starts_with(' ') && (out.is_empty() || out.ends_with(' ')) {
+                    after = &after[1..];
+                } else if after.is_empty() && out.ends_with(' ') {
+                    out.pop();
+                }
+            }
+            (Some(n), Some(prefix)) => out.push_str(&format!("{prefix}{n}")),
+            (Some(n), None) => out.push_str(&n.to_string()),
+            // Unknown placeholder: leave it untouched
+            (None, _) => out.push_str(&rest[start..=start + len]),
+        }
+        rest = after;
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sessions(statuses: &[Status]) -> Vec<Session> {
+        statuses
+            .iter()
+            .map(|&status| Session {
+                status,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn counts_and_prefixes() {
+        let sessions = sessions(&[Status::Attention, Status::Working, Status::Working]);
+        assert_eq!(render("{attention?!}/{working}/{total}", &sessions), "!1/2/3");
+    }
+
+    #[test]
+    fn unclosed_brace_is_literal() {
+        assert_eq!(render("ab{c", &[]), "ab{c");
+        assert_eq!(render("{total} ab{c", &[]), "0 ab{c");
+    }
+
+    #[test]
+    fn unknown_placeholder_is_kept() {
+        assert_eq!(render("{nope} {total}", &[]), "{nope} 0");
+    }
+
+    #[test]
+    fn empty_segments_drop_their_gap() {
+        let sessions = sessions(&[Status::Attention, Status::Idle]);
+        let format = "{attention?A} {working?W} {idle?I}";
+        assert_eq!(render(format, &sessions), "A1 I1");
+        assert_eq!(render("{working?W} {idle?I}", &sessions), "I1");
+        assert_eq!(render("{idle?I} {working?W}", &sessions), "I1");
+        assert_eq!(render("{working?W} {idle?I}", &[]), "");
+    }
+
+    #[test]
+    fn user_spacing_is_kept() {
+        let sessions = sessions(&[Status::Working]);
+        assert_eq!(render("  {working}  of {total} ", &sessions), "  1  of 1 ");
+        assert_eq!(render("#[fg=red]  {working?W}", &sessions), "#[fg=red]  W1");
+    }
+}
@@ -1,68 +1,206 @@
-use crate::protocol::ServerMessage;
-use crate::session::{Session, disambiguate_slugs};
+use std::collections::{BTreeMap, HashSet};
+
+use crate::mux::MuxKind;
+use crate::protocol::{ControlCommand, PromptAnswer, ServerMessage};
+use crate::session::{HistoryEvent, Session, Status, slug_from_cwd, sort_sessions};
+
+/// A visible line in the session table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RowItem {
+    /// Repository section header. `key` is the repo root, or empty for sessions outside git.
+    Group {
+        key: String,
+        name: String,
+        /// Session counts indexed as attention, working, idle.
+        counts: [usize; 3],
+        collapsed: bool,
+    },
+    /// Index into `App::sessions`.
+    Session(usize),
+}
+
+/// What the input prompt does: change a field, or send the input to Claude.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptKind {
+    Rename,
+    Labels,
+    /// Type the input into the session's pane as a prompt for Claude.
+    Send,
+}
+
+/// An open input prompt for one session.
+pub struct Prompt {
+    pub kind: PromptKind,
+    pub session_id: String,
+    pub slug: String,
+    pub input: String,
+    /// Apply to every session in the same working directory rather than just this one
+    /// (names and labels only).
+    pub dir: bool,
+}
+
+/// A permission answer waiting for the user to confirm it.
+pub struct Confirm {
+    pub session_id: String,
+    pub slug: String,
+    pub answer: PromptAnswer,
+    /// Tool that asked for permission.
+    pub tool: Option<String>,
+    /// Notification text that came with the request.
+    pub message: Option<String>,
+}
 
 pub struct App {
     pub sessions: Vec<Session>,
+    /// Table rows derived from `sessions`, grouped by repository unless `grouped` is off.
+    pub rows: Vec<RowItem>,
+    pub grouped: bool,
+    collapsed: HashSet<String>,
+    /// Index into `rows`.
     pub cursor: usize,
     pub should_quit: bool,
+    pub show_details: bool,
+    pub show_history: bool,
+    /// Session the history pane currently shows (or is waiting for).
+    pub history_session: Option<String>,
+    pub history: Vec<HistoryEvent>,
+    history_stale: bool,
+    pub prompt: Option<Prompt>,
+    pub confirm: Option<Confirm>,
+    /// Error from the last command sent to the daemon, shown until the next key press.
+    pub notice: Option<String>,
 }
 
 impl App {
     pub fn new() -> Self {
         Self {
             sessions: Vec::new(),
+            rows: Vec::new(),
+            grouped: true,
+            collapsed: HashSet::new(),
             cursor: 0,
             should_quit: false,
+            show_details: false,
+            show_history: false,
+            history_session: None,
+            history: Vec::new(),
+            history_stale: false,
+            prompt: None,
+            confirm: None,
+            notice: None,
         }
     }
 
     /// Apply a server message and update local state.
     pub fn apply(&mut self, msg: ServerMessage) {
+        let selected_key = self.row_key(self.cursor);
+
         match msg {
             ServerMessage::Snapshot { sessions } => {
                 self.sessions = sessions;
             }
             ServerMessage::Update { session } => {
+                if self.history_session.as_ref() == Some(&session.session_id) {
+                    self.history_stale = true;
+                }
                 if let Some(existing) = self
                     .sessions
                     .iter_mut()
                     .find(|s| s.session_id == session.session_id)
                 {
-                    *existing = session;
+                    *existing = *session;
                 } else {
-                    self.sessions.push(session);
+                    self.sessions.push(*session);
                 }
             }
             ServerMessage::Remove { session_id } => {
                 self.sessions.retain(|s| s.session_id != session_id);
             }
+            ServerMessage::History { session_id, events }
+                if self.history_session.as_ref() == Some(&session_id) =>
+            {
+                self.history = events;
+            }
+            ServerMessage::Error { message } => {
+                self.notice = Some(message);
+            }
+            // Replies to one-off requests never arrive on the subscription
+            _ => {}
         }
 
-        // Sort: Attention first, then Working, then Idle
-        self.sessions.sort_by(|a, b| {
-            fn priority(s: &Session) -> u8 {
-                match s.status {
-                    crate::session::Status::Attention => 0,
-                    crate::session::Status::Working => 1,
-                    crate::session::Status::Idle => 2,
-                }
-            }
-            priority(a)
-                .cmp(&priority(b))
-                .then(a.slug.cmp(&b.slug))
-        });
+        sort_sessions(&mut self.sessions);
 
-        // Disambiguate slugs
-        disambiguate_slugs(&mut self.sessions);
+        self.rebuild_rows(selected_key);
+    }
+
+    /// Recompute visible rows, keeping the cursor on the same session or group if it still exists.
+    fn rebuild_rows(&mut self, selected_key: Option<String>) {
+        self.rows = if self.grouped {
+            self.grouped_rows()
+        } else {
+            (0..self.sessions.len()).map(RowItem::Session).collect()
+        };
+
+        if let Some(i) = selected_key
+            .as_ref()
+            .and_then(|key| (0..self.rows.len()).find(|&i| self.row_key(i).as_ref() == Some(key)))
+        {
+            self.cursor = i;
+        }
 
         // Clamp cursor
-        if !self.sessions.is_empty() {
-            self.cursor = self.cursor.min(self.sessions.len() - 1);
+        if !self.rows.is_empty() {
+            self.cursor = self.cursor.min(self.rows.len() - 1);
         } else {
             self.cursor = 0;
         }
     }
 
+    /// Sessions grouped under their repository root (worktrees share one root), groups sorted
+    /// by name with sessions outside git last. Session order within a group is preserved.
+    fn grouped_rows(&self) -> Vec<RowItem> {
+        // Keyed by (is_other, lowercase name, repo root) so BTreeMap order is display order
+        let mut groups: BTreeMap<(bool, String, String), (String, Vec<usize>)> = BTreeMap::new();
+        for (i, session) in self.sessions.iter().enumerate() {
+            let (key, name) = match &session.git_repo {
+                Some(repo) => (repo.clone(), slug_from_cwd(repo)),
+                None => (String::new(), "Other".to_string()),
+            };
+            groups
+                .entry((key.is_empty(), name.to_lowercase(), key))
+                .or_insert_with(|| (name, Vec::new()))
+                .1
+                .push(i);
+        }
+
+        let mut rows = Vec::new();
+        for ((_, _, key), (name, members)) in groups {
+            let mut counts = [0; 3];
+            for &i in &members {
+                counts[self.sessions[i].status.priority() as usize] += 1;
+            }
+            let collapsed = self.collapsed.contains(&key);
+            rows.push(RowItem::Group {
+                key,
+                name,
+                counts,
+                collapsed,
+            });
+            if !collapsed {
+                rows.extend(members.into_iter().map(RowItem::Session));
+            }
+        }
+        rows
+    }
+
+    /// Stable identity of a row: `s:<session_id>` or `g:<repo>`.
+    fn row_key(&self, row: usize) -> Option<String> {
+        match self.rows.get(row)? {
+            RowItem::Group { key, .. } => Some(format!("g:{key}")),
+            RowItem::Session(i) => self.sessions.get(*i).map(|s| format!("s:{}", s.session_id)),
+        }
+    }
+
     pub fn move_up(&mut self) {
         if self.cursor > 0 {
             self.cursor -= 1;
@@ -70,15 +208,152 @@ impl App {
     }
 
     pub fn move_down(&mut self) {
-        if !self.sessions.is_empty() {
-            self.cursor = (self.cursor + 1) % self.sessions.len();
+        if !self.rows.is_empty() {
+            self.cursor = (self.cursor + 1) % self.rows.len();
         }
     }
 
-    /// Get the tmux pane of the selected session.
-    pub fn selected_pane(&self) -> Option<&str> {
-        self.sessions
-            .get(self.cursor)
-            .and_then(|s| s.tmux_pane.as_deref())
+    pub fn selected(&self) -> Option<&Session> {
+        match self.rows.get(self.cursor)? {
+            RowItem::Session(i) => self.sessions.get(*i),
+            RowItem::Group { .. } => None,
+        }
+    }
+
+    /// Whether the cursor is on a repository header.
+    pub fn on_group_header(&self) -> bool {
+        matches!(self.rows.get(self.cursor), Some(RowItem::Group { .. }))
+    }
+
+    pub fn toggle_grouped(&mut self) {
+        let selected_key = self.row_key(self.cursor);
+        self.grouped = !self.grouped;
+        self.rebuild_rows(selected_key);
+    }
+
+    /// Collapse or expand the group under the cursor (its header or one of its sessions).
+    pub fn toggle_collapse(&mut self) {
+        if !self.grouped {
+            return;
+        }
+        let key = match self.rows.get(self.cursor) {
+            Some(RowItem::Group { key, .. }) => key.clone(),
+            Some(RowItem::Session(i)) => self.sessions[*i].git_repo.clone().unwrap_or_default(),
+            None => return,
+        };
+        if !self.collapsed.remove(&key) {
+            self.collapsed.insert(key.clone());
+        }
+        // Keep the cursor on the header of the toggled group
+        self.rebuild_rows(Some(format!("g:{key}")));
+    }
+
+    pub fn toggle_details(&mut self) {
+        self.show_details = !self.show_details;
+    }
+
+    pub fn toggle_history(&mut self) {
+        self.show_history = !self.show_history;
+        self.history_stale = self.show_history;
+    }
+
+    /// Return the session whose history should be fetched, if the pane is open and
+    /// the selection changed or the selected session received new events.
+    pub fn take_history_request(&mut self) -> Option<String> {
+        if !self.show_history {
+            return None;
+        }
+        let selected = self.selected().map(|s| s.session_id.clone());
+        if selected == self.history_session && !self.history_stale {
+            return None;
+        }
+        if selected != self.history_session {
+            self.history.clear();
+        }
+        self.history_session = selected.clone();
+        self.history_stale = false;
+        selected
+    }
+
+    /// Open the input prompt for the selected session, pre-filled with its current value.
+    pub fn open_prompt(&mut self, kind: PromptKind) {
+        let Some(session) = self.selected() else {
+            return;
+        };
+        let input = match kind {
+            PromptKind::Rename => session.display_name.clone().unwrap_or_default(),
+            PromptKind::Labels => session.labels.join(" "),
+            PromptKind::Send => String::new(),
+        };
+        self.prompt = Some(Prompt {
+            kind,
+            session_id: session.session_id.clone(),
+            slug: session.slug.clone(),
+            input,
+            dir: false,
+        });
+    }
+
+    /// Close the prompt and return the command that applies its input.
+    /// An empty input clears the name or labels, and sends nothing.
+    pub fn submit_prompt(&mut self) -> Option<ControlCommand> {
+        let Prompt {
+            kind,
+            session_id,
+            input,
+            dir,
+            ..
+        } = self.prompt.take()?;
+        Some(match kind {
+            PromptKind::Rename => ControlCommand::RenameSession {
+                session: session_id,
+                name: Some(input),
+                dir,
+            },
+            PromptKind::Labels => ControlCommand::SetLabels {
+                session: session_id,
+                labels: vec![input],
+                dir,
+            },
+            PromptKind::Send if input.trim().is_empty() => return None,
+            PromptKind::Send => ControlCommand::SendPrompt {
+                session: session_id,
+                text: input,
+            },
+        })
+    }
+
+    /// Ask to confirm answering the selected session's permission prompt.
+    pub fn open_confirm(&mut self, answer: PromptAnswer) {
+        let Some(session) = self.selected() else {
+            return;
+        };
+        if session.status != Status::Attention {
+            self.notice = Some(format!("{} isn't waiting for permission", session.slug));
+            return;
+        }
+        self.confirm = Some(Confirm {
+            session_id: session.session_id.clone(),
+            slug: session.slug.clone(),
+            answer,
+            tool: session.current_tool.clone().or_else(|| session.last_tool.clone()),
+            message: session.last_message.clone(),
+        });
+    }
+
+    /// Close the confirmation and return the command that sends the answer.
+    pub fn submit_confirm(&mut self) -> Option<ControlCommand> {
+        let Confirm {
+            session_id, answer, ..
+        } = self.confirm.take()?;
+        Some(ControlCommand::AnswerPrompt {
+            session: session_id,
+            answer,
+        })
+    }
+
+    /// Get the multiplexer pane of the selected session.
+    pub fn selected_pane(&self) -> Option<(MuxKind, &str)> {
+        self.selected().and_then(|s| s.pane())
     }
 }
@@ -2,33 +2,46 @@ mod app;
 mod ui;
 
 use std::io;
+use std::path::Path;
 
-use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
 use crossterm::terminal::{
     EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
 };
 use ratatui::DefaultTerminal;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::UnixStream;
+use tokio::sync::mpsc;
 
-use crate::protocol::{SOCKET_PATH, ServerMessage};
+use crate::config::TuiConfig;
+use crate::mux::MuxKind;
+use crate::protocol::{
+    ClientHello, ControlCommand, Hello, PromptAnswer, ServerMessage, check_handshake,
+    check_socket_owner,
+};
 
-use app::App;
+use app::{App, PromptKind};
+use ui::Theme;
 
-pub async fn run() {
-    if let Err(e) = run_inner().await {
+pub async fn run(socket: &Path, config: &TuiConfig) {
+    if let Err(e) = run_inner(socket, config).await {
         eprintln!("tui error: {e}");
     }
 }
 
-async fn run_inner() -> anyhow::Result<()> {
+async fn run_inner(socket: &Path, config: &TuiConfig) -> anyhow::Result<()> {
+    let theme = Theme::from_config(config);
+
     // Connect to daemon
-    let stream = UnixStream::connect(SOCKET_PATH).await?;
+    check_socket_owner(socket)?;
+    let stream = UnixStream::connect(socket).await?;
     let (reader, mut writer) = stream.into_split();
     let mut reader = BufReader::new(reader);
 
-    // Send subscribe hello
-    writer.write_all(b"{\"type\":\"subscribe\"}\n").await?;
+    // Send subscribe hello and check the daemon speaks our protocol
+    let hello = Hello::new(ClientHello::Subscribe).to_line()?;
+    writer.write_all(hello.as_bytes()).await?;
+    let pending = read_handshake(&mut reader).await?;
 
     // Setup terminal
     enable_raw_mode()?;
@@ -36,7 +49,10 @@ async fn run_inner() -> anyhow::Result<()> {
     let mut terminal = ratatui::init();
 
     let mut app = App::new();
-    let result = event_loop(&mut terminal, &mut app, &mut reader).await;
+    if let Some(msg) = pending {
+        app.apply(msg);
+    }
+    let result = event_loop(&mut terminal, &mut app, &theme, &mut reader, socket).await;
 
     // Restore terminal
     ratatui::restore();
@@ -49,12 +65,15 @@ async fn run_inner() -> anyhow::Result<()> {
 async fn event_loop(
     terminal: &mut DefaultTerminal,
     app: &mut App,
+    theme: &Theme,
     reader: &mut BufReader<tokio::net::unix::OwnedReadHalf>,
+    socket: &Path,
 ) -> anyhow::Result<()> {
     let mut line_buf = String::new();
+    let (reply_tx, mut reply_rx) = mpsc::unbounded_channel::<ServerMessage>();
 
     // Initial draw
-    terminal.draw(|f| ui::draw(f, app))?;
+    terminal.draw(|f| ui::draw(f, app, theme))?;
 
     loop {
         tokio::select! {
@@ -68,7 +87,8 @@ async fn event_loop(
                     Ok(_) => {
                         if let Ok(msg) = serde_json::from_str::<ServerMessage>(line_buf.trim()) {
                             app.apply(msg);
-                            terminal.draw(|f| ui::draw(f, app))?;
+                            request_history(app, socket, &reply_tx);
+                            terminal.draw(|f| ui::draw(f, app, theme))?;
                         }
                         line_buf.clear();
                     }
@@ -78,6 +98,12 @@ async fn event_loop(
                 }
             }
 
+            // History replies and command errors from short-lived query connections
+            Some(msg) = reply_rx.recv() => {
+                app.apply(msg);
+                terminal.draw(|f| ui::draw(f, app, theme))?;
+            }
+
             // Handle keyboard input (poll with timeout for responsiveness)
             _ = tokio::task::spawn_blocking(|| event::poll(std::time::Duration::from_millis(100))) => {
                 // Check for available events without blocking
@@ -86,6 +112,42 @@ async fn event_loop(
                         if key.kind != KeyEventKind::Press {
                             continue;
                         }
+                        app.notice = None;
+                        if app.confirm.is_some() {
+                            // Anything but an explicit yes cancels
+                            if key.code == KeyCode::Char('y')
+                                && let Some(cmd) = app.submit_confirm()
+                            {
+                                send_command(socket, cmd, &reply_tx);
+                            }
+                            app.confirm = None;
+                            continue;
+                        }
+                        if let Some(prompt) = app.prompt.as_mut() {
+                            match key.code {
+                                KeyCode::Esc => app.prompt = None,
+                                KeyCode::Enter
+                                    if prompt.kind == PromptKind::Send
+                                        && key.modifiers.contains(KeyModifiers::ALT) =>
+                                {
+                                    prompt.input.push('\n');
+                                }
+                                KeyCode::Enter => {
+                                    if let Some(cmd) = app.submit_prompt() {
+                                        send_command(socket, cmd, &reply_tx);
+                                    }
+                                }
+                                KeyCode::Tab if prompt.kind != PromptKind::Send => {
+                                    prompt.dir = !prompt.dir;
+                                }
+                                KeyCode::Backspace => {
+                                    prompt.input.pop();
+                                }
+                                KeyCode::Char(c) => prompt.input.push(c),
+                                _ => {}
+                            }
+                            continue;
+                        }
                         match key.code {
                             KeyCode::Char('q') | KeyCode::Esc => {
                                 app.should_quit = true;
@@ -96,11 +158,45 @@ async fn event_loop(
                             KeyCode::Char('k') | KeyCode::Up => {
                                 app.move_up();
                             }
+                            KeyCode::Char('i') => {
+                                app.toggle_details();
+                            }
+                            KeyCode::Char('h') => {
+                                app.toggle_history();
+                            }
+                            KeyCode::Char('r') => {
+                                app.open_prompt(PromptKind::Rename);
+                            }
+                            KeyCode::Char('l') => {
+                                app.open_prompt(PromptKind::Labels);
+                            }
+                            KeyCode::Char('s') => {
+                                app.open_prompt(PromptKind::Send);
+                            }
+                            KeyCode::Char('y') => {
+                                app.open_confirm(PromptAnswer::Approve);
+                            }
+                            KeyCode::Char('a') => {
+                                app.open_confirm(PromptAnswer::ApproveAlways);
+                            }
+                            KeyCode::Char('n') => {
+                                app.open_confirm(PromptAnswer::Deny);
+                            }
+                            KeyCode::Char('g') => {
+                                app.toggle_grouped();
+                            }
+                            KeyCode::Char(' ') => {
+                                app.toggle_collapse();
+                            }
                             KeyCode::Enter => {
-                                if let Some(pane) = app.selected_pane() {
-                                    switch_to_pane(pane);
+                                if app.on_group_header() {
+                                    app.toggle_collapse();
+                                } else {
+                                    if let Some((mux, pane)) = app.selected_pane() {
+                                        focus_pane(mux, pane, &reply_tx);
+                                    }
+                                    app.move_down();
                                 }
-                                app.move_down();
                             }
                             _ => {}
                         }
@@ -111,7 +207,8 @@ async fn event_loop(
                     break;
                 }
 
-                terminal.draw(|f| ui::draw(f, app))?;
+                request_history(app, socket, &reply_tx);
+                terminal.draw(|f| ui::draw(f, app, theme))?;
             }
         }
     }
@@ -119,9 +216,80 @@ async fn event_loop(
     Ok(())
 }
 
-fn switch_to_pane(pane_id: &str) {
-    // switch-client handles cross-session jumps (select-pane/select-window don't)
-    let _ = std::process::Command::new("tmux")
-        .args(["switch-client", "-t", pane_id])
-        .status();
+/// Fetch the selected session's history in the background if the pane needs it.
+fn request_history(app: &mut App, socket: &Path, tx: &mpsc::UnboundedSender<ServerMessage>) {
+    let Some(session_id) = app.take_history_request() else {
+        return;
+    };
+    let socket = socket.to_path_buf();
+    let tx = tx.clone();
+    tokio::spawn(async move {
+        if let Ok(msg) = query(&socket, ClientHello::History { session_id }).await {
+            let _ = tx.send(msg);
+        }
+    });
+}
+
+/// Focus a pane in the background, since Zellij may need many steps to reach it; failures
+/// come back as `Error` messages.
+fn focus_pane(mux: MuxKind, pane: &str, tx: &mpsc::UnboundedSender<ServerMessage>) {
+    let pane = pane.to_string();
+    let tx = tx.clone();
+    tokio::spawn(async move {
+        let result = tokio::task::spawn_blocking(move || mux.backend().focus_pane(&pane)).await;
+        let error = match result {
+            Ok(Ok(())) => return,
+            Ok(Err(e)) => e.to_string(),
+            Err(e) => e.to_string(),
+        };
+        let message = format!("{}: {error}", mux.label());
+        let _ = tx.send(ServerMessage::Error { message });
+    });
+}
+
+/// Send a control command in the background; failures come back as `Error` messages.
+/// Successful changes arrive as updates on the subscription.
+fn send_command(socket: &Path, cmd: ControlCommand, tx: &mpsc::UnboundedSender<ServerMessage>) {
+    let socket = socket.to_path_buf();
+    let tx = tx.clone();
+    tokio::spawn(async move {
+        let reply = query(&socket, ClientHello::Command(cmd))
+            .await
+            .unwrap_or_else(|e| ServerMessage::Error {
+                message: e.to_string(),
+            });
+        if let ServerMessage::Error { .. } = reply {
+            let _ = tx.send(reply);
+        }
+    });
+}
+
+/// Send one request to the daemon on a short-lived connection and return its reply.
+async fn query(socket: &Path, request: ClientHello) -> anyhow::Result<ServerMessage> {
+    check_socket_owner(socket)?;
+    let stream = UnixStream::connect(socket).await?;
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+
+    let hello = Hello::new(request).to_line()?;
+    writer.write_all(hello.as_bytes()).await?;
+
+    if let Some(msg) = read_handshake(&mut reader).await? {
+        return Ok(msg);
+    }
+    let mut line = String::new();
+    reader.read_line(&mut line).await?;
+    Ok(serde_json::from_str(line.trim())?)
+}
+
+/// Read the daemon's reply to our `Hello`; returns the first real message if the daemon
+/// predates the handshake.
+async fn read_handshake(
+    reader: &mut BufReader<tokio::net::unix::OwnedReadHalf>,
+) -> anyhow::Result<Option<ServerMessage>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).await? == 0 {
+        anyhow::bail!("daemon closed the connection");
+    }
+    check_handshake(serde_json::from_str(line.trim())?)
 }
@@ -1,15 +1,57 @@
-use chrono::Utc;
+use std::str::FromStr;
+
+use chrono::{DateTime, Local, Utc};
 use ratatui::Frame;
 use ratatui::layout::{Constraint, Layout, Rect};
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span};
-use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Table};
+use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Table, Wrap};
+
+use crate::config::TuiConfig;
+use crate::session::{Session, SessionFlag, Status, format_elapsed, format_tokens, relative_time};
+
+use super::app::{App, PromptKind, RowItem};
 
-use crate::session::{Status, format_tokens, relative_time};
+/// Display settings resolved from the `[tui]` config section.
+pub struct Theme {
+    pub accent: Color,
+    pub working: Color,
+    pub attention: Color,
+    pub idle: Color,
+    pub muted: Color,
+    /// Tools running longer than this are highlighted.
+    pub long_tool: chrono::Duration,
+}
+
+impl Theme {
+    pub fn from_config(config: &TuiConfig) -> Self {
+        let defaults = TuiConfig::default();
+        let parse = |value: &str, fallback: &str| {
+            Color::from_str(value).unwrap_or_else(|_| {
+                eprintln!("unknown colour {value:?}, using {fallback}");
+                Color::from_str(fallback).unwrap_or(Color::Reset)
+            })
+        };
+        Self {
+            accent: parse(&config.accent, &defaults.accent),
+            working: parse(&config.working, &defaults.working),
+            attention: parse(&config.attention, &defaults.attention),
+            idle: parse(&config.idle, &defaults.idle),
+            muted: parse(&config.muted, &defaults.muted),
+            long_tool: chrono::Duration::seconds(config.long_tool_secs as i64),
+        }
+    }
 
-use super::app::App;
+    pub fn status(&self, status: Status) -> Color {
+        match status {
+            Status::Working => self.working,
+            Status::Attention => self.attention,
+            Status::Idle => self.idle,
+        }
+    }
+}
 
-pub fn draw(f: &mut Frame, app: &App) {
+pub fn draw(f: &mut Frame, app: &App, theme: &Theme) {
     let chunks = Layout::vertical([
         Constraint::Length(1), // title bar
         Constraint::Min(3),   // table
@@ -17,22 +59,43 @@ pub fn draw(f: &mut Frame, app: &App) {
     ])
     .split(f.area());
 
-    draw_title(f, chunks[0], app);
-    draw_table(f, chunks[1], app);
-    draw_help(f, chunks[2]);
+    draw_title(f, chunks[0], app, theme);
+    if app.show_details || app.show_history {
+        let panes = Layout::vertical([
+            Constraint::Min(3),         // table
+            Constraint::Percentage(45), // details / history
+        ])
+        .split(chunks[1]);
+        draw_table(f, panes[0], app, theme);
+
+        match (app.show_details, app.show_history) {
+            (true, true) => {
+                let side =
+                    Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                        .split(panes[1]);
+                draw_details(f, side[0], app, theme);
+                draw_history(f, side[1], app, theme);
+            }
+            (true, false) => draw_details(f, panes[1], app, theme),
+            _ => draw_history(f, panes[1], app, theme),
+        }
+    } else {
+        draw_table(f, chunks[1], app, theme);
+    }
+    draw_help(f, chunks[2], app, theme);
 }
 
-fn draw_title(f: &mut Frame, area: Rect, app: &App) {
+fn draw_title(f: &mut Frame, area: Rect, app: &App, theme: &Theme) {
     let count = app.sessions.len();
     let left = Span::styled(
         " BottyCall",
         Style::default()
-            .fg(Color::Cyan)
+            .fg(theme.accent)
             .add_modifier(Modifier::BOLD),
     );
     let right = Span::styled(
         format!("{count} session{} ", if count == 1 { "" } else { "s" }),
-        Style::default().fg(Color::DarkGray),
+        Style::default().fg(theme.muted),
     );
 
     // Pad the middle
@@ -45,7 +108,7 @@ fn draw_title(f: &mut Frame, area: Rect, app: &App) {
     f.render_widget(Paragraph::new(line), area);
 }
 
-fn draw_table(f: &mut Frame, area: Rect, app: &App) {
+fn draw_table(f: &mut Frame, area: Rect, app: &App, theme: &Theme) {
     let now = Utc::now();
 
     let header = Row::new(vec![
@@ -57,34 +120,49 @@ fn draw_table(f: &mut Frame, area: Rect, app: &App) {
     .height(1);
 
     let rows: Vec<Row> = app
-        .sessions
+        .rows
         .iter()
         .enumerate()
-        .map(|(i, session)| {
+        .map(|(i, item)| {
             let selected = i == app.cursor;
             let marker = if selected { ">" } else { " " };
-            let name = format!("{marker} {}", session.slug);
-
-            let status_color = match session.status {
-                Status::Working => Color::Yellow,
-                Status::Attention => Color::Magenta,
-                Status::Idle => Color::Green,
-            };
-
-            let status_text = format!("{} {}", session.status.icon(), session.status.label());
-            let token_text = format_tokens(session.input_tokens + session.output_tokens);
-            let time_text = relative_time(session.last_activity, now);
-
             let style = if selected {
                 Style::default().add_modifier(Modifier::BOLD)
             } else {
                 Style::default()
             };
 
+            let session = match item {
+                RowItem::Group {
+                    name,
+                    counts,
+                    collapsed,
+                    ..
+                } => return group_row(marker, name, *counts, *collapsed, style, theme),
+                RowItem::Session(idx) => &app.sessions[*idx],
+            };
+
+            // Grouped rows sit under their repo header; show the branch to tell worktrees apart
+            let indent = if app.grouped { "   " } else { " " };
+            let mut spans = vec![Span::styled(format!("{marker}{indent}{}", session.slug), style)];
+            if let (Some(branch), true) = (&session.git_branch, app.grouped)
+                && !session.slug.ends_with(&format!("@{branch}"))
+            {
+                spans.push(Span::styled(format!("  {branch}"), Style::default().fg(theme.muted)));
+            }
+            for label in &session.labels {
+                spans.push(Span::styled(format!(" #{label}"), Style::default().fg(theme.accent)));
+            }
+            let name = Line::from(spans);
+
+            let status_cell = status_cell(session, now, theme);
+            let token_text = format_tokens(session.input_tokens + session.output_tokens);
+            let time_text = relative_time(session.last_activity, now);
+
             Row::new(vec![
-                Cell::from(name).style(style),
-                Cell::from(status_text).style(Style::default().fg(status_color)),
-                Cell::from(token_text).style(Style::default().fg(Color::DarkGray)),
+                Cell::from(name),
+                status_cell,
+                Cell::from(token_text).style(Style::default().fg(theme.muted)),
                 Cell::from(time_text).style(Style::default().fg(Color::Gray)),
             ])
         })
@@ -104,13 +182,266 @@ fn draw_table(f: &mut Frame, area: Rect, app: &App) {
     f.render_widget(table, area);
 }
 
-fn draw_help(f: &mut Frame, area: Rect) {
+/// Status column: the running tool and its duration while one is active, flagged when it
+/// has been running longer than the configured threshold, or compaction while it runs.
+/// Running subagents are counted after it, and stalled or forgotten sessions are marked.
+fn status_cell<'a>(session: &Session, now: DateTime<Utc>, theme: &Theme) -> Cell<'a> {
+    let mut style = Style::default().fg(theme.status(session.status));
+    let mut text = match (&session.compacting, &session.current_tool, session.tool_started) {
+        (Some(_), _, _) => format!("{} Compacting", session.status.icon()),
+        (None, Some(tool), Some(started)) => {
+            let text = format!(
+                "{} {tool} — {}",
+                session.status.icon(),
+                format_elapsed(started, now)
+            );
+            if now - started > theme.long_tool {
+                style = Style::default().fg(theme.attention).add_modifier(Modifier::BOLD);
+                format!("{text} !")
+            } else {
+                text
+            }
+        }
+        _ => format!("{} {}", session.status.icon(), session.status.label()),
+    };
+    if session.subagents > 0 {
+        text.push_str(&format!(" +{} agents", session.subagents));
+    }
+    match session.flag {
+        Some(SessionFlag::Stalled) => {
+            text.push_str(" · stalled");
+            style = Style::default().fg(theme.attention).add_modifier(Modifier::BOLD);
+        }
+        Some(SessionFlag::Forgotten) => {
+            text.push_str(" · forgotten");
+            style = Style::default().fg(theme.muted).add_modifier(Modifier::ITALIC);
+        }
+        None => {}
+    }
+    Cell::from(text).style(style)
+}
+
+/// Repository header row: collapse arrow, name and per-status counts.
+fn group_row<'a>(
+    marker: &str,
+    name: &str,
+    counts: [usize; 3],
+    collapsed: bool,
+    style: Style,
+    theme: &Theme,
+) -> Row<'a> {
+    let arrow = if collapsed { "▸" } else { "▾" };
+    let mut spans = Vec::new();
+    for (status, count) in [Status::Attention, Status::Working, Status::Idle]
+        .into_iter()
+        .zip(counts)
+    {
+        if count > 0 {
+            spans.push(Span::styled(
+                format!("{}{count} ", status.icon()),
+                Style::default().fg(theme.status(status)),
+            ));
+        }
+    }
+
+    Row::new(vec![
+        Cell::from(format!("{marker} {arrow} {name}")).style(style.fg(theme.accent)),
+        Cell::from(Line::from(spans)),
+        Cell::from(""),
+        Cell::from(""),
+    ])
+}
+
+fn draw_details(f: &mut Frame, area: Rect, app: &App, theme: &Theme) {
+    let block = Block::default().borders(Borders::TOP).title(" Details ");
+    let Some(session) = app.selected() else {
+        f.render_widget(block, area);
+        return;
+    };
+
+    let now = Utc::now();
+    let since = session
+        .status_since
+        .map(|t| relative_time(t, now))
+        .unwrap_or_default();
+    let status = format!("{} {} ({since})", session.status.icon(), session.status.label());
+
+    let field = |label: &str, value: Option<&str>| {
+        Line::from(vec![
+            Span::styled(format!(" {label:<9}"), Style::default().fg(theme.muted)),
+            Span::raw(value.unwrap_or("-").to_string()),
+        ])
+    };
+
+    let lines = vec![
+        Line::from(vec![
+            Span::styled(format!(" {:<9}", "Status"), Style::default().fg(theme.muted)),
+            Span::styled(status, Style::default().fg(theme.status(session.status))),
+            Span::styled(
+                session.flag.map(|f| format!("  {}", f.label())).unwrap_or_default(),
+                Style::default().fg(theme.attention),
+            ),
+        ]),
+        field("Session", Some(&session.session_id)),
+        field("Cwd", session.cwd.as_deref()),
+        field("Repo", session.git_repo.as_deref()),
+        field("Branch", session.git_branch.as_deref()),
+        field(
+            "Pane",
+            session
+                .pane()
+                .map(|(mux, pane)| format!("{pane} ({})", mux.label()))
+                .as_deref(),
+        ),
+        field("Tty", session.tty.as_deref()),
+        field("Model", session.model.as_deref()),
+        field(
+            "Context",
+            session.context_left.map(|p| format!("{p}% left")).as_deref(),
+        ),
+        field("Cost", session.cost_usd.map(|c| format!("${c:.2}")).as_deref()),
+        field(
+            "Labels",
+            (!session.labels.is_empty())
+                .then(|| session.labels.join(", "))
+                .as_deref(),
+        ),
+        match (&session.current_tool, session.tool_started) {
+            (Some(tool), Some(started)) => field(
+                "Tool",
+                Some(&format!("{tool} (running {})", format_elapsed(started, now))),
+            ),
+            _ => field("Tool", session.last_tool.as_deref()),
+        },
+        field("Message", session.last_message.as_deref()),
+        field(
+            "Agents",
+            (session.subagents > 0)
+                .then(|| format!("{} running", session.subagents))
+                .as_deref(),
+        ),
+        match (&session.compacting, session.last_compaction) {
+            (Some(trigger), _) => field("Compact", Some(&format!("in progress ({trigger})"))),
+            (None, Some(at)) => field(
+                "Compact",
+                Some(&format!("last at {}", at.with_timezone(&Local).format("%H:%M"))),
+            ),
+            (None, None) => field("Compact", None),
+        },
+    ];
+
+    f.render_widget(
+        Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: false }),
+        area,
+    );
+}
+
+fn draw_history(f: &mut Frame, area: Rect, app: &App, theme: &Theme) {
+    let now = Utc::now();
+    let title = app
+        .selected()
+        .map(|s| format!(" History: {} ", s.slug))
+        .unwrap_or_else(|| " History ".to_string());
+
+    // Newest first so the latest activity is always visible
+    let lines: Vec<Line> = app
+        .history
+        .iter()
+        .rev()
+        .map(|e| {
+            let mut spans = vec![
+                Span::styled(
+                    format!(" {:>8}  ", relative_time(e.ts, now)),
+                    Style::default().fg(theme.muted),
+                ),
+                Span::styled(e.event.clone(), Style::default().fg(theme.accent)),
+            ];
+            if let Some(tool) = &e.tool_name {
+                spans.push(Span::raw(format!("  {tool}")));
+            }
+            if let Some(message) = &e.message {
+                spans.push(Span::styled(
+                    format!("  {message}"),
+                    Style::default().fg(Color::Gray),
+                ));
+            }
+            Line::from(spans)
+        })
+        .collect();
+
+    let block = Block::default().borders(Borders::TOP).title(title);
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Bottom line: the answer confirmation or input prompt when open, else the last command
+/// error, else key help.
+fn draw_help(f: &mut Frame, area: Rect, app: &App, theme: &Theme) {
+    if let Some(confirm) = &app.confirm {
+        let asked = match (&confirm.tool, &confirm.message) {
+            (Some(tool), Some(message)) => format!("{tool}: {message}"),
+            (Some(tool), None) => tool.clone(),
+            (None, Some(message)) => message.clone(),
+            (None, None) => "permission prompt".to_string(),
+        };
+        let line = Line::from(vec![
+            Span::styled(
+                format!(" {} for {}? ", confirm.answer.label(), confirm.slug),
+                Style::default().fg(theme.attention),
+            ),
+            Span::raw(asked),
+            Span::styled("  y confirm  any key cancel", Style::default().fg(theme.muted)),
+        ]);
+        f.render_widget(Paragraph::new(line), area);
+        return;
+    }
+    if let Some(prompt) = &app.prompt {
+        let scope = if prompt.dir { "directory" } else { "session" };
+        let (what, keys) = match prompt.kind {
+            PromptKind::Rename => (format!("Name ({scope})"), "Tab scope  Enter save"),
+            PromptKind::Labels => (format!("Labels ({scope})"), "Tab scope  Enter save"),
+            PromptKind::Send => ("Prompt".to_string(), "Alt+Enter newline  Enter send"),
+        };
+        let line = Line::from(vec![
+            Span::styled(
+                format!(" {what} for {}: ", prompt.slug),
+                Style::default().fg(theme.accent),
+            ),
+            Span::raw(format!("{}▏", prompt.input.replace('\n', "⏎"))),
+            Span::styled(format!("  {keys}  Esc cancel"), Style::default().fg(theme.muted)),
+        ]);
+        f.render_widget(Paragraph::new(line), area);
+        return;
+    }
+    if let Some(notice) = &app.notice {
+        let line = Line::styled(format!(" {notice}"), Style::default().fg(theme.attention));
+        f.render_widget(Paragraph::new(line), area);
+        return;
+    }
+
     let help = Line::from(vec![
-        Span::styled(" j/k", Style::default().fg(Color::Cyan)),
+        Span::styled(" j/k", Style::default().fg(theme.accent)),
         Span::raw(" navigate  "),
-        Span::styled("Enter", Style::default().fg(Color::Cyan)),
+        Span::styled("Enter", Style::default().fg(theme.accent)),
         Span::raw(" switch  "),
-        Span::styled("q", Style::default().fg(Color::Cyan)),
+        Span::styled("i", Style::default().fg(theme.accent)),
+        Span::raw(" details  "),
+        Span::styled("r", Style::default().fg(theme.accent)),
+        Span::raw(" rename  "),
+        Span::styled("l", Style::default().fg(theme.accent)),
+        Span::raw(" labels  "),
+        Span::styled("s", Style::default().fg(theme.accent)),
+        Span::raw(" send  "),
+        Span::styled("y/a/n", Style::default().fg(theme.accent)),
+        Span::raw(" approve/always/deny  "),
+        Span::styled("g", Style::default().fg(theme.accent)),
+        Span::raw(" group  "),
+        Span::styled("Space", Style::default().fg(theme.accent)),
+        Span::raw(" fold  "),
+        Span::styled("h", Style::default().fg(theme.accent)),
+        Span::raw(" history  "),
+        Span::styled("q", Style::default().fg(theme.accent)),
         Span::raw(" quit"),
     ]);
     f.render_widget(Paragraph::new(help), area);
@@ -0,0 +1,128 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::Path;
+
+use chrono::Local;
+
+use crate::client::{Connection, Filter, matches_all};
+use crate::protocol::{ClientHello, ServerMessage};
+use crate::session::Session;
+
+/// Stream session changes to stdout until the daemon goes away or stdout closes.
+pub fn run(socket: &Path, json: bool, filters: &[Filter]) {
+    if let Err(e) = run_inner(socket, json, filters) {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
+}
+
+fn run_inner(socket: &Path, json: bool, filters: &[Filter]) -> anyhow::Result<()> {
+    let mut conn = Connection::open(socket, ClientHello::Subscribe)?;
+    let mut out = std::io::stdout().lock();
+    // Last seen state per session, so removals can be filtered and labelled
+    let mut known: HashMap<String, Session> = HashMap::new();
+    // Sessions last shown as matching, so ones that drift out of the filter get a removal
+    let mut shown: HashSet<String> = HashSet::new();
+
+    while let Some(msg) = conn.recv()? {
+        let lines = match msg {
+            ServerMessage::Snapshot { sessions } => {
+                known = sessions
+                    .iter()
+                    .map(|s| (s.session_id.clone(), s.clone()))
+                    .collect();
+                let sessions: Vec<Session> = sessions
+                    .into_iter()
+                    .filter(|s| matches_all(filters, s))
+                    .collect();
+                shown = sessions.iter().map(|s| s.session_id.clone()).collect();
+                if json {
+                    vec![serde_json::to_string(&ServerMessage::Snapshot { sessions })?]
+                } else {
+                    sessions.iter().map(|s| human("=", s)).collect()
+                }
+            }
+            ServerMessage::Update { session } => {
+                let previous = known.insert(session.session_id.clone(), (*session).clone());
+                if !matches_all(filters, &session) {
+                    if !shown.remove(&session.session_id) {
+                        continue;
+                    }
+                    if json {
+                        let session_id = session.session_id.clone();
+                        vec![serde_json::to_string(&ServerMessage::Remove { session_id })?]
+                    } else {
+                        vec![format!(
+                            "{} - {}  no longer matches",
+                            Local::now().format("%H:%M:%S"),
+                            session.slug
+                        )]
+                    }
+                } else if json {
+                    shown.insert(session.session_id.clone());
+                    vec![serde_json::to_string(&ServerMessage::Update { session })?]
+                } else if shown.insert(session.session_id.clone()) {
+                    // Just came into the filter, so show it even if nothing else changed
+                    vec![human(" ", &session)]
+                } else if previous.is_some_and(|p| {
+                    p.status == session.status
+                        && p.current_tool == session.current_tool
+                        && p.flag == session.flag
+                }) {
+                    // Token and metadata refreshes are noise in the human view
+                    continue;
+                } else {
+                    vec![human(" ", &session)]
+                }
+            }
+            ServerMessage::Remove { session_id } => {
+                let Some(session) = known.remove(&session_id) else {
+                    continue;
+                };
+                if !shown.remove(&session_id) {
+                    continue;
+                }
+                if json {
+                    vec![serde_json::to_string(&ServerMessage::Remove { session_id })?]
+                } else {
+                    vec![format!(
+                        "{} - {}  ended",
+                        Local::now().format("%H:%M:%S"),
+                        session.slug
+                    )]
+                }
+            }
+            _ => continue,
+        };
+
+        for line in lines {
+            // A closed pipe (e.g. `| head`) ends the watch quietly
+            if writeln!(out, "{line}").is_err() {
+                return Ok(());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// `HH:MM:SS <mark> slug  ⚡ Working  Bash` — `=` marks the initial snapshot.
+fn human(mark: &str, session: &Session) -> String {
+    let mut line = format!(
+        "{} {mark} {}  {} {}",
+        Local::now().format("%H:%M:%S"),
+        session.slug,
+        session.status.icon(),
+        session.status.label()
+    );
+    if let Some(flag) = session.flag {
+        line.push_str(&format!(" ({})", flag.label()));
+    }
+    if let Some(tool) = &session.current_tool {
+        line.push_str(&format!("  {tool}"));
+    }
+    if let Some(branch) = &session.git_branch {
+        line.push_str(&format!("  ({branch})"));
+    }
+    line
+}